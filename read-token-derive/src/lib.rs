@@ -22,25 +22,77 @@ pub fn read_token(input: TokenStream) -> TokenStream {
 fn impl_read_token_lifetime(ast: &syn::DeriveInput) -> quote::Tokens {
     let name = &ast.ident;
     quote! {
-        impl<'t> ReadToken<'t> for #name<'t> {
+        impl<'t> ::lexp::ReadToken<'t> for #name<'t> {
             type Token = #name<'t>;
 
-            fn read_token_caps(&self, _: &str, _: &[&str]) -> Self::Token {
+            fn read_token_caps(&self, _: &str, _: ::lexp::Captures) -> Self::Token {
                 self.clone()
             }
         }
+
+        impl<'t> ::lexp::bytes::ReadByteToken<'t> for #name<'t> {
+            type Token = #name<'t>;
+
+            fn read_byte_token(
+                &self,
+                _: &'t [u8],
+            ) -> ::std::result::Result<Self::Token, ::lexp::TokenError> {
+                ::std::result::Result::Ok(self.clone())
+            }
+        }
+
+        impl<'t> ::lexp::TryReadToken<'t> for #name<'t> {
+            type Token = #name<'t>;
+        }
+
+        impl<'t, C> ::lexp::TryReadTokenWith<'t, C> for #name<'t> {
+            fn try_read_token_with(
+                &self,
+                _: &str,
+                _: ::lexp::Captures,
+                _: &mut C,
+            ) -> ::std::result::Result<Self::Token, ::lexp::TokenError> {
+                ::std::result::Result::Ok(self.clone())
+            }
+        }
     }
 }
 
 fn impl_read_token(ast: &syn::DeriveInput) -> quote::Tokens {
     let name = &ast.ident;
     quote! {
-        impl ReadToken<'static> for #name {
+        impl<'t> ::lexp::ReadToken<'t> for #name {
             type Token = #name;
 
-            fn read_token_caps(&self, _: &str, _: &[&str]) -> Self::Token {
+            fn read_token_caps(&self, _: &str, _: ::lexp::Captures) -> Self::Token {
                 self.clone()
             }
         }
+
+        impl<'t> ::lexp::bytes::ReadByteToken<'t> for #name {
+            type Token = #name;
+
+            fn read_byte_token(
+                &self,
+                _: &'t [u8],
+            ) -> ::std::result::Result<Self::Token, ::lexp::TokenError> {
+                ::std::result::Result::Ok(self.clone())
+            }
+        }
+
+        impl<'t> ::lexp::TryReadToken<'t> for #name {
+            type Token = #name;
+        }
+
+        impl<'t, C> ::lexp::TryReadTokenWith<'t, C> for #name {
+            fn try_read_token_with(
+                &self,
+                _: &str,
+                _: ::lexp::Captures,
+                _: &mut C,
+            ) -> ::std::result::Result<Self::Token, ::lexp::TokenError> {
+                ::std::result::Result::Ok(self.clone())
+            }
+        }
     }
 }
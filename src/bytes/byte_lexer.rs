@@ -0,0 +1,218 @@
+use super::byte_pattern::ReadBytes;
+use crate::error::{Furthest, TokenError};
+use crate::lexer::Lexeme;
+use crate::modes::Action;
+use crate::parse::{Lexed, Parse, ParseWith};
+use crate::read_token::TokenResult;
+
+/// Conversion of the matched bytes into a token
+///
+/// Closures like `|b: &[u8]| ...` may return a token or a `Result`.
+/// The derive of `ReadToken` implements this trait too.
+///
+pub trait ReadByteToken<'t> {
+    type Token;
+    fn read_byte_token(&self, bytes: &'t [u8]) -> Result<Self::Token, TokenError>;
+}
+
+impl<'t, O, F> ReadByteToken<'t> for F
+where
+    O: TokenResult<'t>,
+    F: Fn(&'t [u8]) -> O,
+{
+    type Token = O::Token;
+
+    fn read_byte_token(&self, bytes: &'t [u8]) -> Result<Self::Token, TokenError> {
+        self(bytes).into_token()
+    }
+}
+
+/// `()` makes no data, like the constructor of a skipped lexeme
+impl<'t> ReadByteToken<'t> for () {
+    type Token = ();
+
+    fn read_byte_token(&self, _bytes: &'t [u8]) -> Result<(), TokenError> {
+        Ok(())
+    }
+}
+
+/// Makes a lexeme of bytes, which lexers tokenize as `[u8]` input
+///
+/// Lexemes of bytes combine into `Lexer`, `Longest` and `Modes` like lexemes
+/// of text, and `ParseIterator` makes the same results of them with spans
+/// in bytes.
+///
+pub fn lex_bytes<'t, P, R>(read_bytes: P, read_token: R) -> Lexeme<P, R>
+where
+    P: ReadBytes,
+    R: ReadByteToken<'t>,
+{
+    Lexeme {
+        read_pattern: read_bytes,
+        read_token,
+        priority: 0,
+        action: Action::None,
+        name: None,
+    }
+}
+
+impl<'t, P, R> Parse<'t, [u8]> for Lexeme<P, R>
+where
+    P: ReadBytes,
+    R: ReadByteToken<'t>,
+{
+    type Token = R::Token;
+
+    fn read_furthest(&self, bytes: &'t [u8], _mode: usize) -> Furthest {
+        Furthest::new(self.read_pattern.read_bytes_furthest(bytes), self.name)
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    fn action(&self) -> Action {
+        self.action
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn read_len(&self, bytes: &'t [u8], _mode: usize) -> Option<usize> {
+        self.read_pattern.read_bytes(bytes)
+    }
+}
+
+/// Constructors of bytes take no context, so they parse with any one
+impl<'t, C, P, R> ParseWith<'t, C, [u8]> for Lexeme<P, R>
+where
+    P: ReadBytes,
+    R: ReadByteToken<'t>,
+{
+    fn parse_action(
+        &self,
+        bytes: &'t [u8],
+        _pos: usize,
+        _mode: usize,
+        _ctx: &mut C,
+    ) -> Option<Lexed<Self::Token>> {
+        let len = self.read_pattern.read_bytes(bytes)?;
+        let tok = self.read_token.read_byte_token(&bytes[..len]);
+        Some((tok.map(Some), len, self.action))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes::bpat;
+    use crate::lexer::skip;
+    use crate::modes::Modes;
+    use crate::parse::ParseResult;
+    use crate::patterns::ANY;
+    use crate::recovery::SkipChar;
+    use crate::span::Span;
+
+    #[derive(Clone, PartialEq, Debug, ReadToken)]
+    enum Token<'t> {
+        Magic,
+        Length(u16),
+        Payload(&'t [u8]),
+        End,
+    }
+
+    fn payload<'t>(bytes: &'t [u8]) -> Token<'t> {
+        Token::Payload(&bytes[1..bytes.len() - 1])
+    }
+
+    #[test]
+    fn byte_lexer() {
+        let length = bpat(b'\x01') & (bpat(ANY) * 2);
+        let payload_bytes = bpat(b'\x02') & bpat(ANY).until(b'\x03');
+        let lx = lex_bytes(b"\x89LX", Token::Magic)
+            | lex_bytes(length, |b: &[u8]| {
+                Token::Length(u16::from_be_bytes([b[1], b[2]]))
+            })
+            | lex_bytes(payload_bytes, payload).named("payload")
+            | lex_bytes(b'\x03', Token::End);
+
+        let bytes: &[u8] = b"\x89LX\x01\x00\xff\x02\xc3\x28\x03\x03";
+        let tokens: Vec<_> = lx.tokenize(bytes).collect();
+        assert_eq!(
+            tokens,
+            [
+                ParseResult::Ok(Token::Magic, Span::of(bytes, 0..3)),
+                ParseResult::Ok(Token::Length(255), Span::of(bytes, 3..6)),
+                ParseResult::Ok(Token::Payload(b"\xc3\x28"), Span::of(bytes, 6..10)),
+                ParseResult::Ok(Token::End, Span::of(bytes, 10..11)),
+            ]
+        );
+
+        let error = match lx.tokenize(&b"\x89LX\x02ab"[..]).last() {
+            Some(ParseResult::Error(error)) => error,
+            _ => unreachable!(),
+        };
+        assert!(error.is_unterminated());
+        assert_eq!(error.to_string(), "unterminated payload starting at 3");
+
+        let error = match lx.tokenize(&b"\x89LY"[..]).last() {
+            Some(ParseResult::Error(error)) => error,
+            _ => unreachable!(),
+        };
+        assert_eq!(error.found, Some(0x89));
+        assert_eq!(error.to_string(), "unexpected byte 0x89 at 0");
+    }
+
+    #[test]
+    fn byte_lexer_invalid() {
+        let digits = bpat(b'0'..=b'9') * (1..);
+        let lx = lex_bytes(digits, |b: &[u8]| {
+            std::str::from_utf8(b).unwrap().parse().map(Token::Length)
+        }) | lex_bytes(b' ', Token::End);
+
+        let tokens: Vec<_> = lx.tokenize(&b"1 99999"[..]).collect();
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[2].span().range(), 2..7);
+        assert!(matches!(tokens[2], ParseResult::Invalid(..)));
+    }
+
+    #[test]
+    fn byte_lexer_skip_and_modes() {
+        const FRAME: usize = 1;
+
+        let main = skip(bpat(b' ') * (1..))
+            | lex_bytes(b'\x02', Token::Magic).push(FRAME)
+            | lex_bytes(b'\x03', Token::End);
+        let frame = lex_bytes(b'\x03', Token::End).pop()
+            | lex_bytes(bpat(|b: u8| b != b'\x03') * (1..), Token::Payload);
+
+        let modes = Modes::new(main).mode(frame);
+        let bytes: &[u8] = b" \x02 a\x03 \x03";
+        let tokens: Vec<_> = modes.tokenize(bytes).collect();
+        assert_eq!(
+            tokens,
+            [
+                ParseResult::Ok(Token::Magic, Span::of(bytes, 1..2)),
+                ParseResult::Ok(Token::Payload(b" a"), Span::of(bytes, 2..4)),
+                ParseResult::Ok(Token::End, Span::of(bytes, 4..5)),
+                ParseResult::Ok(Token::End, Span::of(bytes, 6..7)),
+            ]
+        );
+
+        let longest =
+            (lex_bytes(b'\x02', Token::Magic) | lex_bytes(b"\x02\x02", Token::End)).longest();
+        assert_eq!(longest.parse(&b"\x02\x02"[..]), Some((Token::End, 2)));
+
+        let bytes: &[u8] = b"\xff\n\x03";
+        let tokens: Vec<_> = modes.tokenize(bytes).recover(SkipChar).collect();
+        assert_eq!(
+            tokens,
+            [
+                ParseResult::Skipped(Span::of(bytes, 0..2)),
+                ParseResult::Ok(Token::End, Span::of(bytes, 2..3)),
+            ]
+        );
+        assert_eq!(tokens[1].span().start.line, 1);
+    }
+}
@@ -0,0 +1,502 @@
+use crate::patterns::{
+    AndPattern, AnyPattern, BacktrackPattern, ManyPattern, OrPattern, Pattern, RangePattern,
+    UntilPattern,
+};
+use crate::read_pattern::push_len;
+use std::collections::HashSet;
+use std::ops::{Bound, Range, RangeBounds, RangeInclusive};
+
+/// Pattern of bytes, like `ReadPattern` is of text
+pub trait ReadBytes {
+    fn read_bytes(&self, bytes: &[u8]) -> Option<usize>;
+
+    /// Returns every length the pattern can match, in order of preference
+    ///
+    /// This allows `backtrack` to match bytes like `ReadPattern::read_pattern_all` text.
+    fn read_bytes_all(&self, bytes: &[u8]) -> Vec<usize> {
+        self.read_bytes(bytes).into_iter().collect()
+    }
+
+    /// Returns the furthest position the pattern reaches in the bytes
+    fn read_bytes_furthest(&self, bytes: &[u8]) -> usize {
+        self.read_bytes(bytes).unwrap_or(0)
+    }
+
+    fn test_bytes(&self, bytes: &[u8]) -> bool {
+        self.read_bytes(bytes) == Some(bytes.len())
+    }
+}
+
+impl ReadBytes for u8 {
+    fn read_bytes(&self, bytes: &[u8]) -> Option<usize> {
+        match bytes.first() {
+            Some(b) if b == self => Some(1),
+            _ => None,
+        }
+    }
+}
+
+impl ReadBytes for &[u8] {
+    fn read_bytes(&self, bytes: &[u8]) -> Option<usize> {
+        if bytes.starts_with(self) {
+            Some(self.len())
+        } else {
+            None
+        }
+    }
+
+    fn read_bytes_furthest(&self, bytes: &[u8]) -> usize {
+        bytes
+            .iter()
+            .zip(self.iter())
+            .take_while(|(a, b)| a == b)
+            .count()
+    }
+}
+
+impl<const N: usize> ReadBytes for &[u8; N] {
+    fn read_bytes(&self, bytes: &[u8]) -> Option<usize> {
+        (&self[..]).read_bytes(bytes)
+    }
+
+    fn read_bytes_furthest(&self, bytes: &[u8]) -> usize {
+        (&self[..]).read_bytes_furthest(bytes)
+    }
+}
+
+impl<F: Fn(u8) -> bool> ReadBytes for F {
+    fn read_bytes(&self, bytes: &[u8]) -> Option<usize> {
+        match bytes.first() {
+            Some(&b) if self(b) => Some(1),
+            _ => None,
+        }
+    }
+}
+
+impl ReadBytes for Range<u8> {
+    fn read_bytes(&self, bytes: &[u8]) -> Option<usize> {
+        match bytes.first() {
+            Some(b) if self.contains(b) => Some(1),
+            _ => None,
+        }
+    }
+}
+
+impl ReadBytes for RangeInclusive<u8> {
+    fn read_bytes(&self, bytes: &[u8]) -> Option<usize> {
+        match bytes.first() {
+            Some(b) if self.contains(b) => Some(1),
+            _ => None,
+        }
+    }
+}
+
+/// `ANY` matches any byte
+impl ReadBytes for AnyPattern {
+    fn read_bytes(&self, bytes: &[u8]) -> Option<usize> {
+        if bytes.is_empty() {
+            None
+        } else {
+            Some(1)
+        }
+    }
+}
+
+impl<L, R> ReadBytes for AndPattern<L, R>
+where
+    L: ReadBytes,
+    R: ReadBytes,
+{
+    fn read_bytes(&self, bytes: &[u8]) -> Option<usize> {
+        let len_a = self.0.read_bytes(bytes)?;
+        let len_b = self.1.read_bytes(&bytes[len_a..])?;
+        Some(len_a + len_b)
+    }
+
+    fn read_bytes_all(&self, bytes: &[u8]) -> Vec<usize> {
+        let mut lens = Vec::new();
+        for len_a in self.0.read_bytes_all(bytes) {
+            for len_b in self.1.read_bytes_all(&bytes[len_a..]) {
+                push_len(&mut lens, len_a + len_b);
+            }
+        }
+
+        lens
+    }
+
+    fn read_bytes_furthest(&self, bytes: &[u8]) -> usize {
+        match self.0.read_bytes(bytes) {
+            Some(len_a) => len_a + self.1.read_bytes_furthest(&bytes[len_a..]),
+            None => self.0.read_bytes_furthest(bytes),
+        }
+    }
+}
+
+impl<L, R> ReadBytes for OrPattern<L, R>
+where
+    L: ReadBytes,
+    R: ReadBytes,
+{
+    fn read_bytes(&self, bytes: &[u8]) -> Option<usize> {
+        self.0
+            .read_bytes(bytes)
+            .or_else(|| self.1.read_bytes(bytes))
+    }
+
+    fn read_bytes_all(&self, bytes: &[u8]) -> Vec<usize> {
+        let mut lens = self.0.read_bytes_all(bytes);
+        for len in self.1.read_bytes_all(bytes) {
+            push_len(&mut lens, len);
+        }
+
+        lens
+    }
+
+    fn read_bytes_furthest(&self, bytes: &[u8]) -> usize {
+        self.0
+            .read_bytes_furthest(bytes)
+            .max(self.1.read_bytes_furthest(bytes))
+    }
+}
+
+impl<T> ManyPattern<T>
+where
+    T: ReadBytes,
+{
+    fn collect_bytes(
+        &self,
+        bytes: &[u8],
+        len: usize,
+        count: u32,
+        visited: &mut HashSet<(usize, u32)>,
+        lens: &mut Vec<usize>,
+    ) {
+        if count == self.1 {
+            push_len(lens, len);
+            return;
+        }
+
+        for l in self.0.read_bytes_all(&bytes[len..]) {
+            if visited.insert((len + l, count + 1)) {
+                self.collect_bytes(bytes, len + l, count + 1, visited, lens);
+            }
+        }
+    }
+}
+
+impl<T> ReadBytes for ManyPattern<T>
+where
+    T: ReadBytes,
+{
+    fn read_bytes(&self, bytes: &[u8]) -> Option<usize> {
+        let mut len = 0;
+        for _ in 0..self.1 {
+            len += self.0.read_bytes(&bytes[len..])?;
+        }
+
+        Some(len)
+    }
+
+    fn read_bytes_all(&self, bytes: &[u8]) -> Vec<usize> {
+        let mut lens = Vec::new();
+        self.collect_bytes(bytes, 0, 0, &mut HashSet::new(), &mut lens);
+        lens
+    }
+
+    fn read_bytes_furthest(&self, bytes: &[u8]) -> usize {
+        let mut len = 0;
+        for _ in 0..self.1 {
+            let rest = &bytes[len..];
+            match self.0.read_bytes(rest) {
+                Some(l) => len += l,
+                None => return len + self.0.read_bytes_furthest(rest),
+            }
+        }
+
+        len
+    }
+}
+
+impl<T, R> RangePattern<T, R>
+where
+    T: ReadBytes,
+    R: RangeBounds<u32>,
+{
+    /// Reads repetitions and returns the length and the count of them
+    fn repeat_bytes(&self, bytes: &[u8]) -> (usize, u32) {
+        if self.0.read_bytes(&[]).is_some() && self.1.end_bound() == Bound::Unbounded {
+            panic!("Infinity loop")
+        }
+
+        let mut len = 0;
+        let mut count = 0;
+        while self.can_repeat(count) {
+            match self.0.read_bytes(&bytes[len..]) {
+                Some(l) => {
+                    len += l;
+                    count += 1;
+                }
+                None => break,
+            }
+        }
+
+        (len, count)
+    }
+
+    fn collect_bytes(
+        &self,
+        bytes: &[u8],
+        len: usize,
+        count: u32,
+        visited: &mut HashSet<(usize, u32)>,
+        lens: &mut Vec<usize>,
+    ) {
+        if self.can_repeat(count) {
+            for l in self.0.read_bytes_all(&bytes[len..]) {
+                // An empty repetition only makes sense to reach the minimum count
+                if l == 0 && self.1.contains(&count) {
+                    continue;
+                }
+
+                let next = self.next_count(count);
+                if visited.insert((len + l, next)) {
+                    self.collect_bytes(bytes, len + l, next, visited, lens);
+                }
+            }
+        }
+
+        if self.1.contains(&count) {
+            push_len(lens, len);
+        }
+    }
+}
+
+impl<T, R> ReadBytes for RangePattern<T, R>
+where
+    T: ReadBytes,
+    R: RangeBounds<u32>,
+{
+    fn read_bytes(&self, bytes: &[u8]) -> Option<usize> {
+        let (len, count) = self.repeat_bytes(bytes);
+        if self.1.contains(&count) {
+            Some(len)
+        } else {
+            None
+        }
+    }
+
+    fn read_bytes_all(&self, bytes: &[u8]) -> Vec<usize> {
+        let mut lens = Vec::new();
+        self.collect_bytes(bytes, 0, 0, &mut HashSet::new(), &mut lens);
+        lens
+    }
+
+    fn read_bytes_furthest(&self, bytes: &[u8]) -> usize {
+        let (len, count) = self.repeat_bytes(bytes);
+        if self.1.contains(&count) {
+            len
+        } else {
+            len + self.0.read_bytes_furthest(&bytes[len..])
+        }
+    }
+}
+
+impl<P, U> UntilPattern<P, U>
+where
+    P: ReadBytes,
+    U: ReadBytes,
+{
+    fn collect_bytes(
+        &self,
+        bytes: &[u8],
+        len: usize,
+        visited: &mut HashSet<usize>,
+        lens: &mut Vec<usize>,
+    ) {
+        let rest = &bytes[len..];
+        let ends = self.1.read_bytes_all(rest);
+        if !ends.is_empty() {
+            for end_len in ends {
+                push_len(lens, len + end_len);
+            }
+
+            return;
+        }
+
+        for l in self.0.read_bytes_all(rest) {
+            if l != 0 && visited.insert(len + l) {
+                self.collect_bytes(bytes, len + l, visited, lens);
+            }
+        }
+    }
+}
+
+/// The repeated pattern must move forward, so an empty match of it fails
+impl<P, U> ReadBytes for UntilPattern<P, U>
+where
+    P: ReadBytes,
+    U: ReadBytes,
+{
+    fn read_bytes(&self, bytes: &[u8]) -> Option<usize> {
+        let mut len = 0;
+
+        loop {
+            let rest = &bytes[len..];
+            if let Some(end_len) = self.1.read_bytes(rest) {
+                break Some(len + end_len);
+            }
+
+            match self.0.read_bytes(rest)? {
+                0 => break None,
+                l => len += l,
+            }
+        }
+    }
+
+    fn read_bytes_all(&self, bytes: &[u8]) -> Vec<usize> {
+        let mut lens = Vec::new();
+        self.collect_bytes(bytes, 0, &mut HashSet::new(), &mut lens);
+        lens
+    }
+
+    fn read_bytes_furthest(&self, bytes: &[u8]) -> usize {
+        let mut len = 0;
+
+        loop {
+            let rest = &bytes[len..];
+            if let Some(end_len) = self.1.read_bytes(rest) {
+                break len + end_len;
+            }
+
+            match self.0.read_bytes(rest) {
+                Some(l) if l != 0 => len += l,
+                _ => {
+                    let furthest = self.0.read_bytes_furthest(rest);
+                    break len + furthest.max(self.1.read_bytes_furthest(rest));
+                }
+            }
+        }
+    }
+}
+
+impl<T> ReadBytes for BacktrackPattern<T>
+where
+    T: ReadBytes,
+{
+    fn read_bytes(&self, bytes: &[u8]) -> Option<usize> {
+        self.0.read_bytes_all(bytes).first().copied()
+    }
+
+    fn read_bytes_all(&self, bytes: &[u8]) -> Vec<usize> {
+        self.0.read_bytes_all(bytes)
+    }
+
+    fn read_bytes_furthest(&self, bytes: &[u8]) -> usize {
+        self.0.read_bytes_furthest(bytes)
+    }
+
+    fn test_bytes(&self, bytes: &[u8]) -> bool {
+        self.0.read_bytes_all(bytes).contains(&bytes.len())
+    }
+}
+
+impl<T> ReadBytes for Pattern<T>
+where
+    T: ReadBytes,
+{
+    fn read_bytes(&self, bytes: &[u8]) -> Option<usize> {
+        self.0.read_bytes(bytes)
+    }
+
+    fn read_bytes_all(&self, bytes: &[u8]) -> Vec<usize> {
+        self.0.read_bytes_all(bytes)
+    }
+
+    fn read_bytes_furthest(&self, bytes: &[u8]) -> usize {
+        self.0.read_bytes_furthest(bytes)
+    }
+
+    fn test_bytes(&self, bytes: &[u8]) -> bool {
+        self.0.test_bytes(bytes)
+    }
+}
+
+/// Wraps a byte pattern to combine it with operators, like `pat` does a text one
+pub fn bpat<T: ReadBytes>(pattern: T) -> Pattern<T> {
+    Pattern(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::ANY;
+
+    #[test]
+    fn byte_patterns() {
+        assert_eq!(b'a'.read_bytes(b"ab"), Some(1));
+        assert_eq!(b'a'.read_bytes(b"b"), None);
+        assert_eq!(b"GET".read_bytes(b"GET /"), Some(3));
+        assert_eq!(b"GET".read_bytes_furthest(b"GEX"), 2);
+        assert_eq!((b'0'..=b'9').read_bytes(b"7"), Some(1));
+        assert_eq!((b'a'..b'f').read_bytes(b"f"), None);
+        assert_eq!((|b: u8| b >= 0x80).read_bytes(b"\xff"), Some(1));
+        assert_eq!(ANY.read_bytes(b"\x00"), Some(1));
+        assert_eq!(ANY.read_bytes(b""), None);
+    }
+
+    #[test]
+    fn combine_byte_patterns() {
+        let digit = bpat(b'0'..=b'9');
+        let number = (bpat(b'-') * ..=1) & (digit.clone() * (1..));
+        assert_eq!(number.read_bytes(b"-12x"), Some(3));
+        assert_eq!(number.read_bytes(b"12"), Some(2));
+        assert_eq!(number.read_bytes(b"-x"), None);
+        assert_eq!(number.read_bytes_furthest(b"-x"), 1);
+
+        let hex2 = (digit | (b'a'..=b'f')) * 2;
+        assert_eq!(hex2.read_bytes(b"f0"), Some(2));
+        assert_eq!(hex2.read_bytes(b"f"), None);
+        assert_eq!(hex2.read_bytes_furthest(b"f"), 1);
+
+        let chunk = bpat(b"\x00\x01") & (bpat(ANY) * (2..3)) & b'\xff';
+        assert!(chunk.test_bytes(b"\x00\x01\xfe\xfe\xff"));
+        assert!(!chunk.test_bytes(b"\x00\x01\xfe\xff"));
+        assert_eq!(chunk.read_bytes_furthest(b"\x00\x01\xfe\xfe\xfe"), 4);
+    }
+
+    #[test]
+    fn backtrack_byte_patterns() {
+        // Like text patterns, repetitions are greedy unless backtracking is on
+        let greedy = (bpat(b'a') * ..) & b'a';
+        assert_eq!(greedy.read_bytes(b"aaa"), None);
+        assert_eq!(greedy.read_bytes_all(b"aaa"), [3, 2, 1]);
+
+        let backtrack = greedy.backtrack();
+        assert_eq!(backtrack.read_bytes(b"aaa"), Some(3));
+        assert!(backtrack.test_bytes(b"a"));
+        assert!(!backtrack.test_bytes(b""));
+
+        let digits = ((bpat(b'0'..=b'9') * (1..)) & b'0').backtrack();
+        assert_eq!(digits.read_bytes(b"1200x"), Some(4));
+        assert_eq!((bpat(b"ab") | b"a").read_bytes_all(b"ab"), [2, 1]);
+        assert_eq!((bpat(ANY) * 2).read_bytes_all(b"abc"), [2]);
+    }
+
+    #[test]
+    fn until_byte_pattern() {
+        let frame = bpat(b'\x02') & bpat(ANY).until(b'\x03');
+        assert!(frame.test_bytes(b"\x02ab\x03"));
+        assert!(frame.test_bytes(b"\x02\x03"));
+        assert_eq!(frame.read_bytes(b"\x02ab"), None);
+        assert_eq!(frame.read_bytes_furthest(b"\x02ab"), 3);
+
+        let pairs = (bpat(b"ab") | b"a").until(b"b!");
+        assert_eq!(pairs.read_bytes(b"ab!"), None);
+        assert_eq!(pairs.read_bytes_all(b"ab!"), [3]);
+
+        // An empty repetition can't reach the end
+        let empty = bpat(b"").until(b';');
+        assert_eq!(empty.read_bytes(b"a;"), None);
+        assert_eq!(empty.read_bytes(b";"), Some(1));
+    }
+}
@@ -0,0 +1,227 @@
+use std::ops::{Index, Range};
+
+#[derive(Clone, Debug, PartialEq)]
+struct Group {
+    name: Option<&'static str>,
+    /// `None` if the group is in a branch which didn't match
+    span: Option<Range<usize>>,
+}
+
+/// Captures of a pattern
+///
+/// Each capture is stored as a byte range relative to the start of the text
+/// passed to `ReadPattern::read_captures`, that is the token start.
+/// When a token is read by `ParseIterator`, the `offset` is the position
+/// of the token in the whole text, so absolute ranges are available too.
+///
+/// A capture can be looked up by its position or, if it was made with
+/// `cap_named`, by its name. Groups of the branch of `|` which didn't match
+/// are unset, so positions of the other groups don't depend on the branch.
+/// Repetitions still push the groups of each iteration.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Captures<'t> {
+    text: &'t str,
+    offset: usize,
+    groups: Vec<Group>,
+}
+
+impl<'t> Captures<'t> {
+    pub fn new(text: &'t str) -> Self {
+        Captures::with_offset(text, 0)
+    }
+
+    pub fn with_offset(text: &'t str, offset: usize) -> Self {
+        Captures {
+            text,
+            offset,
+            groups: Vec::new(),
+        }
+    }
+
+    /// Pushes a capture by its byte range relative to the token start
+    ///
+    /// Panics if the range is out of the text or not on char boundaries.
+    pub fn push(&mut self, span: Range<usize>) {
+        self.push_span(None, span);
+    }
+
+    /// Pushes a named capture by its byte range relative to the token start
+    pub fn push_named(&mut self, name: &'static str, span: Range<usize>) {
+        self.push_span(Some(name), span);
+    }
+
+    fn push_span(&mut self, name: Option<&'static str>, span: Range<usize>) {
+        if self.text.get(span.clone()).is_none() {
+            panic!("Capture {:?} is out of the text", span);
+        }
+
+        self.groups.push(Group {
+            name,
+            span: Some(span),
+        });
+    }
+
+    pub(crate) fn push_unset(&mut self, name: Option<&'static str>) {
+        self.groups.push(Group { name, span: None });
+    }
+
+    pub(crate) fn push_group(&mut self, name: Option<&'static str>, capture: &'t str) {
+        let span = self.span_of(capture);
+        self.push_span(name, span);
+    }
+
+    pub(crate) fn set_len(&mut self, len: usize) {
+        self.text = &self.text[..len];
+    }
+
+    pub(crate) fn set(&mut self, idx: usize, capture: &'t str) {
+        self.groups[idx].span = Some(self.span_of(capture));
+    }
+
+    pub(crate) fn remove(&mut self, range: Range<usize>) {
+        self.groups.drain(range);
+    }
+
+    fn span_of(&self, capture: &'t str) -> Range<usize> {
+        let start = capture.as_ptr() as usize - self.text.as_ptr() as usize;
+        debug_assert!(start + capture.len() <= self.text.len());
+        start..start + capture.len()
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.groups.truncate(len);
+    }
+
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Text of the token
+    pub fn as_str(&self) -> &'t str {
+        self.text
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn get<I: CaptureIndex>(&self, idx: I) -> Option<&'t str> {
+        self.span(idx).map(|span| &self.text[span])
+    }
+
+    /// Byte range of the capture relative to the token start
+    pub fn span<I: CaptureIndex>(&self, idx: I) -> Option<Range<usize>> {
+        let idx = idx.position(self)?;
+        self.groups[idx].span.clone()
+    }
+
+    /// Byte range of the capture in the whole text
+    pub fn absolute_span<I: CaptureIndex>(&self, idx: I) -> Option<Range<usize>> {
+        self.span(idx)
+            .map(|span| span.start + self.offset..span.end + self.offset)
+    }
+
+    /// Name of the capture at the position
+    pub fn name(&self, idx: usize) -> Option<&'static str> {
+        self.groups.get(idx)?.name
+    }
+
+    /// Iterates over the set captures, skipping the unset ones
+    pub fn iter(&self) -> impl Iterator<Item = &'t str> + '_ {
+        self.groups
+            .iter()
+            .filter_map(move |group| Some(&self.text[group.span.clone()?]))
+    }
+
+    pub fn to_vec(&self) -> Vec<&'t str> {
+        self.iter().collect()
+    }
+}
+
+impl<I: CaptureIndex> Index<I> for Captures<'_> {
+    type Output = str;
+
+    fn index(&self, idx: I) -> &Self::Output {
+        match self.get(idx) {
+            Some(capture) => capture,
+            None => panic!("No such capture"),
+        }
+    }
+}
+
+/// Position or name of a capture
+///
+/// If several captures have the same name, for example inside a repetition,
+/// the name refers to the last of them which is set.
+///
+pub trait CaptureIndex {
+    fn position(&self, caps: &Captures) -> Option<usize>;
+}
+
+impl CaptureIndex for usize {
+    fn position(&self, caps: &Captures) -> Option<usize> {
+        if *self < caps.len() {
+            Some(*self)
+        } else {
+            None
+        }
+    }
+}
+
+impl CaptureIndex for &str {
+    fn position(&self, caps: &Captures) -> Option<usize> {
+        caps.groups
+            .iter()
+            .rposition(|group| group.name == Some(*self) && group.span.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures() {
+        let text = "0x1F";
+        let mut caps = Captures::with_offset(text, 10);
+        caps.push(2..4);
+        caps.push(3..4);
+
+        assert_eq!(caps.len(), 2);
+        assert_eq!(caps.get(0), Some("1F"));
+        assert_eq!(&caps[1], "F");
+        assert_eq!(caps.get(2), None);
+        assert_eq!(caps.span(0), Some(2..4));
+        assert_eq!(caps.span(1), Some(3..4));
+        assert_eq!(caps.absolute_span(1), Some(13..14));
+        assert_eq!(caps.to_vec(), ["1F", "F"]);
+
+        caps.truncate(1);
+        assert_eq!(caps.to_vec(), ["1F"]);
+    }
+
+    #[test]
+    fn named_captures() {
+        let text = "1.5e10";
+        let mut caps = Captures::new(text);
+        caps.push_named("int", 0..1);
+        caps.push(2..3);
+        caps.push_named("exp", 4..6);
+
+        assert_eq!(caps.get("int"), Some("1"));
+        assert_eq!(&caps["exp"], "10");
+        assert_eq!(caps.get("frac"), None);
+        assert_eq!(caps.span("exp"), Some(4..6));
+        assert_eq!(caps.name(0), Some("int"));
+        assert_eq!(caps.name(1), None);
+        assert_eq!(caps.get(1), Some("5"));
+
+        caps.push_named("exp", 5..6);
+        assert_eq!(caps.get("exp"), Some("0"));
+    }
+}
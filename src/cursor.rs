@@ -0,0 +1,253 @@
+use crate::parse::{Parse, ParseIterator, ParseResult, ParseWith};
+use crate::span::Span;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+
+/// Position of a `TokenCursor` to rewind to
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Checkpoint(usize);
+
+/// Cursor over tokens for parsers with lookahead and backtracking
+///
+/// Items of the iterator are read lazily and kept in the buffer,
+/// so the cursor can look any number of items ahead and rewind
+/// to any checkpoint.
+///
+pub struct TokenCursor<'p, 't, P, C = ()>
+where
+    P: Parse<'t>,
+{
+    iter: ParseIterator<'p, 't, P, C>,
+    buffer: Vec<ParseResult<P::Token>>,
+    pos: usize,
+}
+
+impl<'p, 't, P, C> TokenCursor<'p, 't, P, C>
+where
+    P: ParseWith<'t, C>,
+{
+    pub fn new(iter: ParseIterator<'p, 't, P, C>) -> Self {
+        TokenCursor {
+            iter,
+            buffer: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Reads items until the one `n` items ahead is buffered
+    fn fill(&mut self, n: usize) -> bool {
+        while self.buffer.len() <= self.pos + n {
+            match self.iter.next() {
+                Some(item) => self.buffer.push(item),
+                None => return false,
+            }
+        }
+
+        true
+    }
+
+    pub fn peek(&mut self) -> Option<&ParseResult<P::Token>> {
+        self.peek_nth(0)
+    }
+
+    /// Returns the item `n` items ahead without consuming it
+    pub fn peek_nth(&mut self, n: usize) -> Option<&ParseResult<P::Token>> {
+        if self.fill(n) {
+            self.buffer.get(self.pos + n)
+        } else {
+            None
+        }
+    }
+
+    /// Consumes the next item
+    pub fn advance(&mut self) -> Option<&ParseResult<P::Token>> {
+        if !self.fill(0) {
+            return None;
+        }
+
+        self.pos += 1;
+        self.buffer.get(self.pos - 1)
+    }
+
+    pub fn is_end(&mut self) -> bool {
+        self.peek().is_none()
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.pos)
+    }
+
+    /// Returns to the checkpoint, so consumed items are read again
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.pos = checkpoint.0;
+    }
+
+    /// Items consumed so far
+    pub fn consumed(&self) -> &[ParseResult<P::Token>] {
+        &self.buffer[..self.pos]
+    }
+
+    /// Span of the last consumed item
+    pub fn last_span(&self) -> Option<Span> {
+        self.consumed().last().map(ParseResult::span)
+    }
+
+    /// Span of items consumed after the checkpoint
+    ///
+    /// If nothing was consumed, the span is empty at the next item.
+    pub fn span_since(&mut self, checkpoint: Checkpoint) -> Span {
+        if checkpoint.0 >= self.pos {
+            let start = self.next_span().start;
+            return Span::new(start, start);
+        }
+
+        let start = self.buffer[checkpoint.0].span().start;
+        let end = self.buffer[self.pos - 1].span().end;
+        Span::new(start, end)
+    }
+
+    /// Span of the next item, or the empty span at the end of the text
+    pub fn next_span(&mut self) -> Span {
+        match self.peek() {
+            Some(item) => item.span(),
+            None => {
+                let end = self.iter.position();
+                Span::new(end, end)
+            }
+        }
+    }
+}
+
+impl<'p, 't, P, C> TokenCursor<'p, 't, P, C>
+where
+    P: ParseWith<'t, C>,
+    P::Token: Clone,
+{
+    /// Consumes the next token if it's of the same kind
+    ///
+    /// Only variants of tokens are compared, so `Token::Number(0)`
+    /// expects any number. Otherwise nothing is consumed and the error
+    /// is boxed, since it keeps the found item.
+    pub fn expect(&mut self, kind: &P::Token) -> ExpectResult<P::Token> {
+        match self.peek() {
+            Some(ParseResult::Ok(tok, span))
+                if mem::discriminant(tok) == mem::discriminant(kind) =>
+            {
+                let found = (tok.clone(), *span);
+                self.pos += 1;
+                Ok(found)
+            }
+            found => {
+                let found = found.cloned();
+                Err(Box::new(ExpectError {
+                    expected: kind.clone(),
+                    found,
+                    span: self.next_span(),
+                }))
+            }
+        }
+    }
+}
+
+/// The expected token with its span, or the error
+pub type ExpectResult<T> = Result<(T, Span), Box<ExpectError<T>>>;
+
+/// Error of a parser, when the next token is not of the expected kind
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpectError<T> {
+    pub expected: T,
+    /// The found item, `None` at the end of the text
+    pub found: Option<ParseResult<T>>,
+    /// Span of the found item, or the empty span at the end of the text
+    pub span: Span,
+}
+
+impl<T> fmt::Display for ExpectError<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected {:?}, found ", self.expected)?;
+        match &self.found {
+            Some(ParseResult::Ok(tok, _)) => write!(f, "{:?}", tok)?,
+            Some(ParseResult::Invalid(error, _)) => write!(f, "invalid token ({})", error)?,
+            Some(ParseResult::Skipped(_)) => write!(f, "unexpected text")?,
+            Some(ParseResult::Error(error)) => return write!(f, "{}", error),
+            None => write!(f, "end of text")?,
+        }
+
+        write!(f, " at {}", self.span.start.offset)
+    }
+}
+
+impl<T> Error for ExpectError<T> where T: fmt::Debug {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+    use crate::patterns::pat;
+    use crate::span::Position;
+
+    #[derive(Copy, Clone, PartialEq, Debug, ReadToken)]
+    enum Token<'t> {
+        Let,
+        Name(&'t str),
+        Eq,
+        Number(&'t str),
+    }
+
+    #[test]
+    fn cursor() {
+        let name = pat('a'..='z') * (1..);
+        let number = pat('0'..='9') * (1..);
+        let lx = lex("let", Token::Let)
+            | lex(name, |n, _| Token::Name(n))
+            | lex('=', Token::Eq)
+            | lex(number, |n, _| Token::Number(n));
+
+        let code = "letx=1";
+        let mut cursor = TokenCursor::new(lx.tokenize(code));
+        assert_eq!(
+            cursor.peek_nth(2),
+            Some(&ParseResult::Ok(Token::Eq, Span::of(code, 4..5)))
+        );
+
+        let start = cursor.checkpoint();
+        assert_eq!(
+            cursor.expect(&Token::Let),
+            Ok((Token::Let, Span::of(code, 0..3)))
+        );
+        assert_eq!(
+            cursor.expect(&Token::Name("")),
+            Ok((Token::Name("x"), Span::of(code, 3..4)))
+        );
+        assert_eq!(cursor.span_since(start), Span::of(code, 0..4));
+        assert_eq!(cursor.last_span(), Some(Span::of(code, 3..4)));
+
+        let error = cursor.expect(&Token::Number("")).unwrap_err();
+        assert_eq!(
+            error.found,
+            Some(ParseResult::Ok(Token::Eq, Span::of(code, 4..5)))
+        );
+        assert_eq!(error.to_string(), "expected Number(\"\"), found Eq at 4");
+
+        cursor.rewind(start);
+        assert_eq!(cursor.consumed().len(), 0);
+        assert_eq!(
+            cursor.advance().map(ParseResult::span),
+            Some(Span::of(code, 0..3))
+        );
+
+        while cursor.advance().is_some() {}
+        assert!(cursor.is_end());
+        assert_eq!(cursor.consumed().len(), 4);
+
+        let error = cursor.expect(&Token::Eq).unwrap_err();
+        assert_eq!(error.found, None);
+        let end = Position::at(code, code.len());
+        assert_eq!(error.span, Span::new(end, end));
+        assert_eq!(error.to_string(), "expected Eq, found end of text at 6");
+    }
+}
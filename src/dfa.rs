@@ -0,0 +1,469 @@
+use crate::error::Furthest;
+use crate::first_chars::FirstChars;
+use crate::parse::{Lexed, Parse, ParseWith};
+use crate::regex::Regex;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+const DEAD: u32 = u32::MAX;
+
+#[derive(Default)]
+struct NfaState {
+    eps: Vec<usize>,
+    trans: Vec<(RangeInclusive<char>, usize)>,
+    accept: Option<usize>,
+}
+
+#[derive(Default)]
+struct Nfa {
+    states: Vec<NfaState>,
+}
+
+impl Nfa {
+    fn add(&mut self) -> usize {
+        self.states.push(NfaState::default());
+        self.states.len() - 1
+    }
+
+    fn eps(&mut self, from: usize, to: usize) {
+        self.states[from].eps.push(to);
+    }
+
+    /// Builds a fragment of the regex and returns its start and end states
+    fn build(&mut self, regex: &Regex) -> (usize, usize) {
+        let start = self.add();
+        match regex {
+            Regex::Chars(ranges) => {
+                let end = self.add();
+                for range in ranges {
+                    self.states[start].trans.push((range.clone(), end));
+                }
+
+                (start, end)
+            }
+            Regex::Seq(items) => {
+                let mut end = start;
+                for item in items {
+                    let (s, e) = self.build(item);
+                    self.eps(end, s);
+                    end = e;
+                }
+
+                (start, end)
+            }
+            Regex::Alt(items) => {
+                let end = self.add();
+                for item in items {
+                    let (s, e) = self.build(item);
+                    self.eps(start, s);
+                    self.eps(e, end);
+                }
+
+                (start, end)
+            }
+            Regex::Repeat(item, min, max) => {
+                let mut cur = start;
+                for _ in 0..*min {
+                    let (s, e) = self.build(item);
+                    self.eps(cur, s);
+                    cur = e;
+                }
+
+                let end = self.add();
+                match max {
+                    None => {
+                        let (s, e) = self.build(item);
+                        self.eps(cur, s);
+                        self.eps(e, cur);
+                    }
+                    Some(max) => {
+                        for _ in *min..*max {
+                            let (s, e) = self.build(item);
+                            self.eps(cur, s);
+                            self.eps(cur, end);
+                            cur = e;
+                        }
+                    }
+                }
+
+                self.eps(cur, end);
+                (start, end)
+            }
+        }
+    }
+
+    fn closure(&self, set: &mut Vec<usize>) {
+        let mut stack = set.clone();
+        while let Some(state) = stack.pop() {
+            for &next in &self.states[state].eps {
+                if !set.contains(&next) {
+                    set.push(next);
+                    stack.push(next);
+                }
+            }
+        }
+
+        set.sort_unstable();
+    }
+
+    /// Splits chars of all transitions into classes with the same behavior
+    fn classes(&self) -> Vec<u32> {
+        let mut starts = vec![0];
+        for state in &self.states {
+            for (range, _) in &state.trans {
+                starts.push(*range.start() as u32);
+                starts.push(*range.end() as u32 + 1);
+            }
+        }
+
+        starts.sort_unstable();
+        starts.dedup();
+        starts.retain(|&start| start <= char::MAX as u32);
+        starts
+    }
+}
+
+/// Deterministic automaton compiled from regexes
+///
+/// Every regex is tagged by its index. When several regexes match
+/// the same text, the one with the highest priority wins
+/// and then the first one.
+///
+#[derive(Clone, Debug)]
+pub struct Dfa {
+    starts: Vec<u32>,
+    ascii: Vec<u32>,
+    trans: Vec<u32>,
+    accept: Vec<Option<usize>>,
+}
+
+impl Dfa {
+    pub fn new<I>(regexes: I) -> Self
+    where
+        I: IntoIterator<Item = Regex>,
+    {
+        Dfa::with_priorities(regexes.into_iter().map(|regex| (regex, 0)))
+    }
+
+    pub fn with_priorities<I>(regexes: I) -> Self
+    where
+        I: IntoIterator<Item = (Regex, i32)>,
+    {
+        let mut nfa = Nfa::default();
+        let start = nfa.add();
+        let mut priorities = Vec::new();
+        for (tag, (regex, priority)) in regexes.into_iter().enumerate() {
+            let (s, e) = nfa.build(&regex);
+            nfa.eps(start, s);
+            nfa.states[e].accept = Some(tag);
+            priorities.push(priority);
+        }
+
+        let starts = nfa.classes();
+        let class_of = |ch: u32| starts.partition_point(|&start| start <= ch) - 1;
+        let ascii = (0..128).map(|ch| class_of(ch) as u32).collect();
+
+        let mut start_set = vec![start];
+        nfa.closure(&mut start_set);
+        let mut sets = vec![start_set.clone()];
+        let mut ids = HashMap::new();
+        ids.insert(start_set, 0);
+
+        let mut trans = Vec::new();
+        let mut accept = Vec::new();
+        let mut idx = 0;
+        while idx < sets.len() {
+            let set = sets[idx].clone();
+            let tag = set
+                .iter()
+                .filter_map(|&state| nfa.states[state].accept)
+                .max_by_key(|&tag| (priorities[tag], Reverse(tag)));
+            accept.push(tag);
+
+            for &class in &starts {
+                let mut next = Vec::new();
+                for &state in &set {
+                    for (range, to) in &nfa.states[state].trans {
+                        let contains =
+                            *range.start() as u32 <= class && class <= *range.end() as u32;
+                        if contains && !next.contains(to) {
+                            next.push(*to);
+                        }
+                    }
+                }
+
+                if next.is_empty() {
+                    trans.push(DEAD);
+                    continue;
+                }
+
+                nfa.closure(&mut next);
+                let id = match ids.get(&next) {
+                    Some(&id) => id,
+                    None => {
+                        let id = sets.len() as u32;
+                        ids.insert(next.clone(), id);
+                        sets.push(next);
+                        id
+                    }
+                };
+
+                trans.push(id);
+            }
+
+            idx += 1;
+        }
+
+        Dfa {
+            starts,
+            ascii,
+            trans,
+            accept,
+        }
+    }
+
+    fn class_of(&self, ch: char) -> usize {
+        if ch.is_ascii() {
+            self.ascii[ch as usize] as usize
+        } else {
+            self.starts.partition_point(|&start| start <= ch as u32) - 1
+        }
+    }
+
+    /// Finds the longest match at the start of the text
+    ///
+    /// Returns the length of the match and the tag of the matched regex.
+    pub fn find(&self, text: &str) -> Option<(usize, usize)> {
+        let classes = self.starts.len();
+        let mut state = 0;
+        let mut found = self.accept[0].map(|tag| (0, tag));
+
+        for (pos, ch) in text.char_indices() {
+            state = self.trans[state * classes + self.class_of(ch)] as usize;
+            if state == DEAD as usize {
+                break;
+            }
+
+            if let Some(tag) = self.accept[state] {
+                found = Some((pos + ch.len_utf8(), tag));
+            }
+        }
+
+        found
+    }
+}
+
+/// Lexer compiled into one automaton
+///
+/// The compiled lexer works in the longest match mode.
+/// Its patterns match like regular expressions do, as if every pattern
+/// was wrapped into `backtrack` and the longest match was taken.
+/// Lexemes which can't be compiled, like closures, are interpreted.
+/// It's made by `Longest::compile` only, since a `Lexer` takes the first
+/// match, which the automaton doesn't find.
+///
+pub struct Compiled<'p, 't, T, C = ()> {
+    lexemes: Vec<&'p dyn ParseWith<'t, C, Token = T>>,
+    dfa: Dfa,
+    tags: Vec<usize>,
+    interpreted: Vec<usize>,
+}
+
+impl<'p, 't, T, C> Compiled<'p, 't, T, C> {
+    pub fn new<P>(parser: &'p P) -> Self
+    where
+        P: ParseWith<'t, C, Token = T>,
+    {
+        let mut lexemes = Vec::new();
+        parser.for_each_lexeme_with(&mut |lexeme| lexemes.push(lexeme));
+
+        let mut regexes = Vec::new();
+        let mut tags = Vec::new();
+        let mut interpreted = Vec::new();
+        for (idx, lexeme) in lexemes.iter().enumerate() {
+            match lexeme.to_regex() {
+                Some(regex) => {
+                    regexes.push((regex, lexeme.priority()));
+                    tags.push(idx);
+                }
+                None => interpreted.push(idx),
+            }
+        }
+
+        Compiled {
+            lexemes,
+            dfa: Dfa::with_priorities(regexes),
+            tags,
+            interpreted,
+        }
+    }
+
+    /// Finds the index of the matched lexeme and the length of the match
+    fn find(&self, text: &'t str, mode: usize) -> Option<(usize, usize)> {
+        let key = |idx: usize, len: usize| (len, self.lexemes[idx].priority(), Reverse(idx));
+
+        let mut best = self.dfa.find(text).map(|(len, tag)| (self.tags[tag], len));
+
+        for &idx in &self.interpreted {
+            if let Some(len) = self.lexemes[idx].read_len(text, mode) {
+                match best {
+                    Some((b, l)) if key(b, l) >= key(idx, len) => {}
+                    _ => best = Some((idx, len)),
+                }
+            }
+        }
+
+        best
+    }
+}
+
+impl<'p, 't, T, C> Parse<'t> for Compiled<'p, 't, T, C> {
+    type Token = T;
+
+    fn read_len(&self, text: &'t str, mode: usize) -> Option<usize> {
+        self.find(text, mode).map(|(_, len)| len)
+    }
+
+    fn read_furthest(&self, text: &'t str, mode: usize) -> Furthest {
+        self.lexemes
+            .iter()
+            .fold(Furthest::default(), |furthest, lexeme| {
+                furthest.merge(lexeme.read_furthest(text, mode))
+            })
+    }
+
+    fn has_modes(&self) -> bool {
+        self.lexemes.iter().any(|lexeme| lexeme.has_modes())
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        self.lexemes.iter().fold(FirstChars::none(), |set, lexeme| {
+            set.union(lexeme.first_chars())
+        })
+    }
+}
+
+impl<'p, 't, T, C> ParseWith<'t, C> for Compiled<'p, 't, T, C> {
+    fn parse_action(
+        &self,
+        text: &'t str,
+        pos: usize,
+        mode: usize,
+        ctx: &mut C,
+    ) -> Option<Lexed<T>> {
+        let (idx, len) = self.find(text, mode)?;
+        let lexeme = self.lexemes[idx];
+
+        // Parsers like `Modes` know the action only after the match
+        if self.interpreted.contains(&idx) {
+            return lexeme
+                .parse_action(text, pos, mode, ctx)
+                .filter(|(_, l, _)| *l == len);
+        }
+
+        let tok = lexeme.parse_exact(text, len, pos, mode, ctx)?;
+        Some((tok, len, lexeme.action()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::captures::Captures;
+    use crate::lexer::lex;
+    use crate::parse::ParseResult;
+    use crate::patterns::{cap, pat, ANY};
+    use crate::span::Span;
+    use crate::ReadPattern;
+
+    #[test]
+    fn dfa() {
+        let dfa = Dfa::new(vec![Regex::str("+"), Regex::str("+=")]);
+        assert_eq!(dfa.find("+=1"), Some((2, 1)));
+        assert_eq!(dfa.find("+1"), Some((1, 0)));
+        assert_eq!(dfa.find("1"), None);
+        assert_eq!(dfa.find(""), None);
+
+        // Unlike the interpreter, the automaton finds the longest match
+        let pattern = pat('/') & (pat(ANY) * ..) & '/';
+        let dfa = Dfa::new(pattern.to_regex());
+        assert_eq!(pattern.read_pattern("/a/b"), None);
+        assert_eq!(dfa.find("/a/b"), Some((3, 0)));
+
+        let pattern = (pat('a') * (1..=2)) & (pat("ф") | "") & (pat('b') * ..);
+        let dfa = Dfa::new(pattern.to_regex());
+        assert_eq!(dfa.find("aaa"), Some((2, 0)));
+        assert_eq!(dfa.find("aфbb"), Some(("aфbb".len(), 0)));
+        assert_eq!(dfa.find("ab"), Some((2, 0)));
+        assert_eq!(dfa.find("b"), None);
+
+        let dfa = Dfa::new(vec![Regex::empty(), Regex::never()]);
+        assert_eq!(dfa.find("a"), Some((0, 0)));
+    }
+
+    #[test]
+    fn dfa_priorities() {
+        let name = (pat('a'..='z') * (1..)).to_regex().unwrap();
+        let dfa = Dfa::new(vec![name.clone(), Regex::str("let")]);
+        assert_eq!(dfa.find("let"), Some((3, 0)));
+
+        let dfa = Dfa::with_priorities(vec![(name, 0), (Regex::str("let"), 1)]);
+        assert_eq!(dfa.find("let"), Some((3, 1)));
+        assert_eq!(dfa.find("lets"), Some((4, 0)));
+    }
+
+    #[derive(Copy, Clone, PartialEq, Debug, ReadToken)]
+    enum Token {
+        Plus,
+        PlusEq,
+        Name,
+        Let,
+        Hex(u32),
+        Space,
+        Comment,
+    }
+
+    #[test]
+    fn compiled() {
+        let name = pat('a'..='z') * (1..);
+        let hex = pat("0x") & cap((pat('0'..='9') | ('a'..='f')) * (1..));
+        let comment = pat("/*") & pat(ANY).until("*/");
+        let lx = (lex('+', Token::Plus)
+            | lex("+=", Token::PlusEq)
+            | lex(hex, |_, caps: Captures| {
+                u32::from_str_radix(&caps[0], 16).map(Token::Hex)
+            })
+            | lex(name, Token::Name)
+            | lex("let", Token::Let).priority(1)
+            | lex(char::is_whitespace, Token::Space)
+            | lex(comment, Token::Comment))
+        .longest();
+
+        let compiled = lx.compile();
+        assert_eq!(compiled.parse("+=").unwrap(), (Token::PlusEq, 2));
+        assert_eq!(compiled.parse("0x1fz").unwrap(), (Token::Hex(31), 4));
+        assert_eq!(compiled.parse("let").unwrap(), (Token::Let, 3));
+        assert_eq!(compiled.parse("letter").unwrap(), (Token::Name, 6));
+        assert_eq!(compiled.parse(" ").unwrap(), (Token::Space, 1));
+        assert_eq!(compiled.parse("/* + */").unwrap(), (Token::Comment, 7));
+        assert!(compiled.parse("-").is_none());
+
+        let code = "let a+=0xff /**/";
+        let expected: Vec<_> = lx.tokenize(code).collect();
+        let tokens: Vec<_> = compiled.tokenize(code).collect();
+        assert_eq!(tokens, expected);
+        assert_eq!(
+            tokens[4],
+            ParseResult::Ok(Token::Hex(255), Span::of(code, 7..11))
+        );
+
+        let code = "+0x100000000+";
+        let tokens: Vec<_> = compiled.tokenize(code).collect();
+        assert_eq!(tokens, lx.tokenize(code).collect::<Vec<_>>());
+        match &tokens[1] {
+            ParseResult::Invalid(_, span) => assert_eq!(span.range(), 1..12),
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use crate::error::LexError;
+use crate::span::Span;
+use std::fmt;
+use unicode_segmentation::UnicodeSegmentation;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Diagnostic message about a span of the text
+///
+/// It renders the lines of the span with line numbers and underlines it,
+/// like rustc does:
+///
+/// ```text
+/// error: unterminated comment starting at 4
+///  --> 1:5
+///   |
+/// 1 | x = /* y
+///   |     ^^^^ comment
+/// ```
+///
+pub struct Diagnostic<'a> {
+    text: &'a str,
+    span: Span,
+    message: String,
+    label: Option<String>,
+    file: Option<String>,
+    color: bool,
+}
+
+impl<'a> Diagnostic<'a> {
+    pub fn new<M>(text: &'a str, span: Span, message: M) -> Self
+    where
+        M: Into<String>,
+    {
+        Diagnostic {
+            text,
+            span,
+            message: message.into(),
+            label: None,
+            file: None,
+            color: false,
+        }
+    }
+
+    /// Makes the diagnostic of the lexing error
+    ///
+    /// The span covers the text up to the furthest position lexemes reached,
+    /// or the unexpected char.
+    pub fn from_error(text: &'a str, error: &LexError) -> Self {
+        let len = match error.found {
+            Some(ch) if error.expected.is_empty() => ch.len_utf8(),
+            _ => error.furthest.offset - error.position.offset,
+        };
+
+        let span = Span::new(error.position, error.position.advance(text, len));
+        let diagnostic = Diagnostic::new(text, span, error.to_string());
+        if error.expected.is_empty() {
+            diagnostic
+        } else {
+            diagnostic.label(error.expected.join(" or "))
+        }
+    }
+
+    /// Sets the label under the span
+    pub fn label<L>(mut self, label: L) -> Self
+    where
+        L: Into<String>,
+    {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Sets the file name to show with the position
+    pub fn file<F>(mut self, file: F) -> Self
+    where
+        F: Into<String>,
+    {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Enables ANSI colors
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn render(&self) -> String {
+        self.to_string()
+    }
+
+    fn paint(&self, style: &'static str) -> (&'static str, &'static str) {
+        if self.color {
+            (style, RESET)
+        } else {
+            ("", "")
+        }
+    }
+
+    /// Lines of the span with byte ranges of the span in them
+    fn lines(&self) -> Vec<(usize, &'a str, usize, usize)> {
+        let Span { start, end } = self.span;
+        let line_start = self.text[..start.offset].rfind('\n').map_or(0, |nl| nl + 1);
+
+        let mut lines = Vec::new();
+        let mut offset = line_start;
+        for (n, line) in self.text[line_start..].split('\n').enumerate() {
+            let number = start.line + n;
+            let from = start.offset.max(offset) - offset;
+            let to = end.offset.min(offset + line.len()).max(offset) - offset;
+
+            // A span which ends at the start of a line doesn't show it
+            if number > start.line && to == 0 {
+                break;
+            }
+
+            lines.push((
+                number + 1,
+                line.strip_suffix('\r').unwrap_or(line),
+                from,
+                to,
+            ));
+            offset += line.len() + 1;
+
+            if number >= end.line {
+                break;
+            }
+        }
+
+        lines
+    }
+}
+
+impl fmt::Display for Diagnostic<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (red, red_end) = self.paint(RED);
+        let (blue, blue_end) = self.paint(BLUE);
+        let (bold, bold_end) = self.paint(BOLD);
+
+        let lines = self.lines();
+        let width = lines
+            .last()
+            .map_or(1, |(number, ..)| number.to_string().len());
+        let gutter = " ".repeat(width);
+
+        writeln!(
+            f,
+            "{}error{}{}: {}{}",
+            red, red_end, bold, self.message, bold_end
+        )?;
+
+        let start = self.span.start;
+        write!(f, "{}{}-->{} ", gutter, blue, blue_end)?;
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+        }
+
+        writeln!(f, "{}:{}", start.line + 1, start.column.chars + 1)?;
+        writeln!(f, "{} {}|{}", gutter, blue, blue_end)?;
+
+        for (idx, (number, line, from, to)) in lines.iter().enumerate() {
+            let (from, to) = (*from.min(&line.len()), *to.min(&line.len()));
+            writeln!(
+                f,
+                "{}{:>width$} |{} {}",
+                blue,
+                number,
+                blue_end,
+                line,
+                width = width
+            )?;
+
+            // Tabs are kept to align the underline with the text
+            let mut indent = String::new();
+            for grapheme in line[..from].graphemes(true) {
+                indent.push(if grapheme == "\t" { '\t' } else { ' ' });
+            }
+
+            let carets = "^".repeat(line[from..to].graphemes(true).count().max(1));
+            write!(
+                f,
+                "{} {}|{} {}{}{}{}",
+                gutter, blue, blue_end, indent, red, carets, red_end
+            )?;
+
+            match &self.label {
+                Some(label) if idx + 1 == lines.len() => {
+                    writeln!(f, " {}{}{}", red, label, red_end)?;
+                }
+                _ => writeln!(f)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Furthest;
+    use crate::span::Position;
+
+    #[test]
+    fn diagnostic() {
+        let text = "let x = 1;\nx = /* y";
+        let error = LexError::new(
+            text,
+            Position::at(text, 15),
+            Furthest::new(4, Some("comment")),
+        );
+        let diagnostic = Diagnostic::from_error(text, &error).file("main.x");
+        assert_eq!(
+            diagnostic.render(),
+            "error: unterminated comment starting at 15
+ --> main.x:2:5
+  |
+2 | x = /* y
+  |     ^^^^ comment
+"
+        );
+
+        let error = LexError::new(text, Position::at(text, 9), Furthest::default());
+        assert_eq!(
+            Diagnostic::from_error(text, &error).render(),
+            "error: unexpected ';' at 9
+ --> 1:10
+  |
+1 | let x = 1;
+  |          ^
+"
+        );
+    }
+
+    #[test]
+    fn diagnostic_lines() {
+        let text = "a\n\tbc\nd\ne\nf\ng\nh\ni\nj\nk\nl";
+        let diagnostic = Diagnostic::new(text, Span::of(text, 4..21), "unexpected text");
+        assert_eq!(
+            diagnostic.label("here").render(),
+            "error: unexpected text
+  --> 2:3
+   |
+ 2 | \tbc
+   | \t ^
+ 3 | d
+   | ^
+ 4 | e
+   | ^
+ 5 | f
+   | ^
+ 6 | g
+   | ^
+ 7 | h
+   | ^
+ 8 | i
+   | ^
+ 9 | j
+   | ^
+10 | k
+   | ^ here
+"
+        );
+
+        let diagnostic = Diagnostic::new(text, Span::of(text, 0..2), "unexpected text");
+        assert_eq!(
+            diagnostic.render(),
+            "error: unexpected text
+ --> 1:1
+  |
+1 | a
+  | ^
+"
+        );
+    }
+
+    #[test]
+    fn diagnostic_color() {
+        let text = "ф?";
+        let diagnostic = Diagnostic::new(text, Span::of(text, 2..3), "unexpected '?'").color(true);
+        assert_eq!(
+            diagnostic.render(),
+            "\x1b[1;31merror\x1b[0m\x1b[1m: unexpected '?'\x1b[0m
+ \x1b[1;34m-->\x1b[0m 1:2
+  \x1b[1;34m|\x1b[0m
+\x1b[1;34m1 |\x1b[0m ф?
+  \x1b[1;34m|\x1b[0m  \x1b[1;31m^\x1b[0m
+"
+        );
+    }
+}
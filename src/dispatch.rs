@@ -0,0 +1,237 @@
+use crate::error::Furthest;
+use crate::first_chars::FirstChars;
+use crate::parse::{Lexed, Parse, ParseWith};
+
+const ASCII_LEN: usize = 128;
+
+/// Lexer with a dispatch table by the first character
+///
+/// Every lexeme is tried only on text which can start it,
+/// so a token costs about the number of candidate lexemes
+/// instead of the number of all lexemes.
+/// Candidates are tried in declaration order, like in `Lexer`,
+/// or all of them are measured in the longest match mode, like in `Longest`.
+///
+pub struct Dispatch<'p, 't, T, C = ()> {
+    lexemes: Vec<(&'p dyn ParseWith<'t, C, Token = T>, FirstChars)>,
+    ascii: Vec<Vec<usize>>,
+    non_ascii: Vec<usize>,
+    end: Vec<usize>,
+    longest: bool,
+}
+
+impl<'p, 't, T, C> Dispatch<'p, 't, T, C> {
+    pub fn new<P>(parser: &'p P) -> Self
+    where
+        P: ParseWith<'t, C, Token = T>,
+    {
+        let mut lexemes = Vec::new();
+        parser.for_each_lexeme_with(&mut |lexeme| lexemes.push((lexeme, lexeme.first_chars())));
+
+        let mut ascii = vec![Vec::new(); ASCII_LEN];
+        let mut non_ascii = Vec::new();
+        let mut end = Vec::new();
+        for (idx, (_, first)) in lexemes.iter().enumerate() {
+            for (ch, candidates) in ascii.iter_mut().enumerate() {
+                if first.contains(ch as u8 as char) || first.is_nullable() {
+                    candidates.push(idx);
+                }
+            }
+
+            if !first.is_ascii() || first.is_nullable() {
+                non_ascii.push(idx);
+            }
+
+            if first.is_nullable() {
+                end.push(idx);
+            }
+        }
+
+        Dispatch {
+            lexemes,
+            ascii,
+            non_ascii,
+            end,
+            longest: false,
+        }
+    }
+
+    /// Switches the lexer to the longest match mode
+    pub fn longest(mut self) -> Self {
+        self.longest = true;
+        self
+    }
+
+    fn candidates<'d>(
+        &'d self,
+        text: &str,
+    ) -> impl Iterator<Item = &'d dyn ParseWith<'t, C, Token = T>> + 'd {
+        let (list, ch) = match text.chars().next() {
+            None => (&self.end, None),
+            Some(ch) if ch.is_ascii() => (&self.ascii[ch as usize], None),
+            Some(ch) => (&self.non_ascii, Some(ch)),
+        };
+
+        list.iter().filter_map(move |&idx| {
+            let (lexeme, first) = &self.lexemes[idx];
+            match ch {
+                Some(ch) if !first.contains(ch) && !first.is_nullable() => None,
+                _ => Some(*lexeme),
+            }
+        })
+    }
+
+    fn find_longest(
+        &self,
+        text: &'t str,
+        mode: usize,
+    ) -> Option<(&dyn ParseWith<'t, C, Token = T>, usize)> {
+        let mut best = None;
+        let mut best_key = None;
+        for lexeme in self.candidates(text) {
+            if let Some(len) = lexeme.read_len(text, mode) {
+                let key = Some((len, lexeme.priority()));
+                if key > best_key {
+                    best = Some((lexeme, len));
+                    best_key = key;
+                }
+            }
+        }
+
+        best
+    }
+}
+
+impl<'p, 't, T, C> Parse<'t> for Dispatch<'p, 't, T, C> {
+    type Token = T;
+
+    fn read_len(&self, text: &'t str, mode: usize) -> Option<usize> {
+        if self.longest {
+            return self.find_longest(text, mode).map(|(_, len)| len);
+        }
+
+        self.candidates(text)
+            .find_map(|lexeme| lexeme.read_len(text, mode))
+    }
+
+    fn read_furthest(&self, text: &'t str, mode: usize) -> Furthest {
+        self.lexemes
+            .iter()
+            .fold(Furthest::default(), |furthest, (lexeme, _)| {
+                furthest.merge(lexeme.read_furthest(text, mode))
+            })
+    }
+
+    fn has_modes(&self) -> bool {
+        self.lexemes.iter().any(|(lexeme, _)| lexeme.has_modes())
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        self.lexemes
+            .iter()
+            .fold(FirstChars::none(), |set, (_, first)| {
+                set.union(first.clone())
+            })
+    }
+}
+
+impl<'p, 't, T, C> ParseWith<'t, C> for Dispatch<'p, 't, T, C> {
+    fn parse_action(
+        &self,
+        text: &'t str,
+        pos: usize,
+        mode: usize,
+        ctx: &mut C,
+    ) -> Option<Lexed<T>> {
+        if self.longest {
+            let (lexeme, _) = self.find_longest(text, mode)?;
+            return lexeme.parse_action(text, pos, mode, ctx);
+        }
+
+        self.candidates(text)
+            .find_map(|lexeme| lexeme.parse_action(text, pos, mode, ctx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+    use crate::parse::ParseResult;
+    use crate::patterns::{pat, ANY};
+    use crate::span::Span;
+
+    #[derive(Copy, Clone, PartialEq, Debug, ReadToken)]
+    enum Token {
+        Plus,
+        PlusEq,
+        Name,
+        Let,
+        Num,
+        Comment,
+        Arrow,
+        Other,
+        Empty,
+    }
+
+    #[test]
+    fn dispatch() {
+        let name = pat('a'..='z') * (1..);
+        let num = pat('0'..='9') * (1..);
+        let comment = pat("/*") & pat(ANY).until("*/");
+        let lx = lex('+', Token::Plus)
+            | lex(name, Token::Name)
+            | lex(num, Token::Num)
+            | lex(comment, Token::Comment)
+            | lex('→', Token::Arrow)
+            | lex(|ch: char| !ch.is_ascii(), Token::Other);
+
+        let dispatch = Dispatch::new(&lx);
+        assert_eq!(dispatch.parse("+=").unwrap(), (Token::Plus, 1));
+        assert_eq!(dispatch.parse("abc1").unwrap(), (Token::Name, 3));
+        assert_eq!(dispatch.parse("12a").unwrap(), (Token::Num, 2));
+        assert_eq!(dispatch.parse("/* a */").unwrap(), (Token::Comment, 7));
+        assert_eq!(dispatch.parse("→").unwrap(), (Token::Arrow, 3));
+        assert_eq!(dispatch.parse("ф").unwrap(), (Token::Other, 2));
+        assert!(dispatch.parse("/").is_none());
+        assert!(dispatch.parse("").is_none());
+
+        let code = "a+1→";
+        let tokens: Vec<_> = dispatch.tokenize(code).collect();
+        assert_eq!(
+            tokens,
+            [
+                ParseResult::Ok(Token::Name, Span::of(code, 0..1)),
+                ParseResult::Ok(Token::Plus, Span::of(code, 1..2)),
+                ParseResult::Ok(Token::Num, Span::of(code, 2..3)),
+                ParseResult::Ok(Token::Arrow, Span::of(code, 3..6)),
+            ]
+        );
+    }
+
+    #[test]
+    fn dispatch_nullable() {
+        let lx = lex('+', Token::Plus) | lex(pat('a') * .., Token::Empty);
+        let dispatch = Dispatch::new(&lx);
+        assert_eq!(dispatch.parse("+").unwrap(), (Token::Plus, 1));
+        assert_eq!(dispatch.parse("aa").unwrap(), (Token::Empty, 2));
+        assert_eq!(dispatch.parse("b").unwrap(), (Token::Empty, 0));
+        assert_eq!(dispatch.parse("ф").unwrap(), (Token::Empty, 0));
+        assert_eq!(dispatch.parse("").unwrap(), (Token::Empty, 0));
+    }
+
+    #[test]
+    fn dispatch_longest() {
+        let name = pat('a'..='z') * (1..);
+        let lx = lex('+', Token::Plus)
+            | lex("+=", Token::PlusEq)
+            | lex(name, Token::Name)
+            | lex("let", Token::Let).priority(1);
+
+        let dispatch = lx.dispatch().longest();
+        assert_eq!(dispatch.parse("+=").unwrap(), (Token::PlusEq, 2));
+        assert_eq!(dispatch.parse("let").unwrap(), (Token::Let, 3));
+        assert_eq!(dispatch.parse("letter").unwrap(), (Token::Name, 6));
+        assert_eq!(dispatch.read_len("+1", 0), Some(1));
+    }
+}
@@ -0,0 +1,198 @@
+use crate::input::{Input, Unit};
+use crate::span::Position;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+/// How far lexemes reached in a text, which none of them matched
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Furthest {
+    pub len: usize,
+    /// Names of the lexemes which reached the furthest position
+    pub lexemes: Vec<&'static str>,
+}
+
+impl Furthest {
+    pub fn new(len: usize, name: Option<&'static str>) -> Self {
+        let lexemes = match name {
+            Some(name) if len != 0 => vec![name],
+            _ => Vec::new(),
+        };
+
+        Furthest { len, lexemes }
+    }
+
+    /// Keeps the furthest of two, or both lexemes if they reached the same position
+    pub fn merge(mut self, other: Self) -> Self {
+        if other.len > self.len {
+            return other;
+        }
+
+        if other.len == self.len {
+            for name in other.lexemes {
+                if !self.lexemes.contains(&name) {
+                    self.lexemes.push(name);
+                }
+            }
+        }
+
+        self
+    }
+}
+
+/// Error of lexing, when no lexeme matches the text
+///
+/// The unit `U` is `char` for text and `u8` for bytes.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexError<U = char> {
+    /// Position where no lexeme matches
+    pub position: Position,
+    /// The unexpected unit, `None` at the end of the text
+    pub found: Option<U>,
+    /// The furthest position any lexeme reached before failing
+    pub furthest: Position,
+    /// The unit where lexemes failed, `None` at the end of the text
+    pub found_at_furthest: Option<U>,
+    /// Names of the lexemes which were viable up to the furthest position
+    pub expected: Vec<&'static str>,
+}
+
+impl<U> LexError<U> {
+    /// Makes the error at the `position` of the `text`
+    pub fn new<I>(text: &I, position: Position, furthest: Furthest) -> Self
+    where
+        I: Input<Unit = U> + ?Sized,
+    {
+        let end = position.advance(text, furthest.len);
+        LexError {
+            position,
+            found: text.unit_at(position.offset),
+            furthest: end,
+            found_at_furthest: text.unit_at(end.offset),
+            expected: furthest.lexemes,
+        }
+    }
+
+    /// Checks if a lexeme was viable up to the end of the text
+    pub fn is_unterminated(&self) -> bool {
+        !self.expected.is_empty() && self.found_at_furthest.is_none()
+    }
+}
+
+impl<U: Unit> fmt::Display for LexError<U> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let start = self.position.offset;
+        let names = self.expected.join(" or ");
+
+        if self.is_unterminated() {
+            return write!(f, "unterminated {} starting at {}", names, start);
+        }
+
+        if !self.expected.is_empty() {
+            write!(f, "invalid {} starting at {}", names, start)?;
+            if let Some(unit) = self.found_at_furthest {
+                write!(f, ": unexpected ")?;
+                unit.describe(f)?;
+                write!(f, " at {}", self.furthest.offset)?;
+            }
+
+            return Ok(());
+        }
+
+        match self.found {
+            Some(unit) => {
+                write!(f, "unexpected ")?;
+                unit.describe(f)?;
+                write!(f, " at {}", start)
+            }
+            None => write!(f, "unexpected end of text at {}", start),
+        }
+    }
+}
+
+impl<U: Unit> Error for LexError<U> {}
+
+/// Error of a token conversion, like a number literal which overflows
+///
+/// The error of the conversion is shared, so the error can be cloned.
+///
+#[derive(Clone, Debug)]
+pub struct TokenError(Arc<dyn Error + Send + Sync>);
+
+impl TokenError {
+    pub fn new<E>(error: E) -> Self
+    where
+        E: Into<Box<dyn Error + Send + Sync>>,
+    {
+        TokenError(Arc::from(error.into()))
+    }
+
+    /// The error returned by the conversion
+    pub fn inner(&self) -> &(dyn Error + Send + Sync + 'static) {
+        &*self.0
+    }
+}
+
+impl PartialEq for TokenError {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_string() == other.0.to_string()
+    }
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Error for TokenError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.source()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn furthest() {
+        let a = Furthest::new(2, Some("a"));
+        let b = Furthest::new(2, Some("b"));
+        let c = Furthest::new(1, Some("c"));
+        assert_eq!(a.clone().merge(b.clone()).lexemes, ["a", "b"]);
+        assert_eq!(c.clone().merge(a.clone()), a);
+        assert_eq!(a.clone().merge(c), a);
+        assert!(Furthest::new(0, Some("d")).lexemes.is_empty());
+    }
+
+    #[test]
+    fn lex_error() {
+        let text = "a /* b";
+        let position = Position::at(text, 2);
+
+        let error = LexError::new(text, position, Furthest::new(4, Some("comment")));
+        assert_eq!(error.found, Some('/'));
+        assert_eq!(error.furthest.offset, text.len());
+        assert!(error.is_unterminated());
+        assert_eq!(error.to_string(), "unterminated comment starting at 2");
+
+        let error = LexError::new(text, position, Furthest::new(2, Some("comment")));
+        assert_eq!(
+            error.to_string(),
+            "invalid comment starting at 2: unexpected ' ' at 4"
+        );
+
+        let error = LexError::new(text, position, Furthest::default());
+        assert_eq!(error.to_string(), "unexpected '/' at 2");
+    }
+
+    #[test]
+    fn token_error() {
+        let error = TokenError::new("99999".parse::<u8>().unwrap_err());
+        assert_eq!(error.to_string(), "number too large to fit in target type");
+        assert_eq!(error.clone(), error);
+        assert_ne!(error, TokenError::new("other"));
+    }
+}
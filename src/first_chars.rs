@@ -0,0 +1,157 @@
+use std::ops::RangeInclusive;
+
+/// Set of characters which can start a match of a pattern
+///
+/// The set may be wider than the real one, but never narrower,
+/// so a pattern can be skipped when the next character isn't in its set.
+/// A nullable set also means the pattern can match the empty string.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FirstChars {
+    ranges: Vec<RangeInclusive<char>>,
+    any: bool,
+    nullable: bool,
+}
+
+impl FirstChars {
+    /// The pattern never matches
+    pub fn none() -> Self {
+        FirstChars::default()
+    }
+
+    /// The pattern matches the empty string only
+    pub fn empty() -> Self {
+        FirstChars {
+            nullable: true,
+            ..FirstChars::default()
+        }
+    }
+
+    /// The pattern can start with any character or match the empty string
+    pub fn any() -> Self {
+        FirstChars {
+            ranges: Vec::new(),
+            any: true,
+            nullable: true,
+        }
+    }
+
+    /// The pattern can start with any character
+    pub fn any_char() -> Self {
+        FirstChars {
+            any: true,
+            ..FirstChars::default()
+        }
+    }
+
+    pub fn char(ch: char) -> Self {
+        FirstChars::range(ch..=ch)
+    }
+
+    pub fn range(range: RangeInclusive<char>) -> Self {
+        let ranges = if range.is_empty() {
+            Vec::new()
+        } else {
+            vec![range]
+        };
+
+        FirstChars {
+            ranges,
+            ..FirstChars::default()
+        }
+    }
+
+    /// Makes the set nullable, like for an optional pattern
+    pub fn or_empty(mut self) -> Self {
+        self.nullable = true;
+        self
+    }
+
+    /// Union of two sets, like for an alternation of patterns
+    pub fn union(mut self, other: Self) -> Self {
+        self.ranges.extend(other.ranges);
+        self.any |= other.any;
+        self.nullable |= other.nullable;
+        self
+    }
+
+    /// First characters of a sequence, where the `next` follows this one
+    pub fn then(self, next: Self) -> Self {
+        if self.nullable {
+            let nullable = next.nullable;
+            let mut set = self.union(next);
+            set.nullable = nullable;
+            set
+        } else {
+            self
+        }
+    }
+
+    pub fn contains(&self, ch: char) -> bool {
+        self.any || self.ranges.iter().any(|range| range.contains(&ch))
+    }
+
+    pub fn is_nullable(&self) -> bool {
+        self.nullable
+    }
+
+    /// Checks if every character of the set is ASCII
+    pub fn is_ascii(&self) -> bool {
+        !self.any && self.ranges.iter().all(|range| range.end().is_ascii())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::{cap, pat, ANY};
+    use crate::ReadPattern;
+
+    #[test]
+    fn first_chars() {
+        let set = FirstChars::char('a').union(FirstChars::range('0'..='9'));
+        assert!(set.contains('a'));
+        assert!(set.contains('5'));
+        assert!(!set.contains('b'));
+        assert!(!set.is_nullable());
+        assert!(set.is_ascii());
+
+        let set = FirstChars::empty().then(FirstChars::char('ф'));
+        assert!(set.contains('ф'));
+        assert!(!set.is_nullable());
+        assert!(!set.is_ascii());
+
+        let set = FirstChars::char('a').then(FirstChars::char('b'));
+        assert!(!set.contains('b'));
+        assert!(!FirstChars::range('b'..='a').contains('a'));
+        assert!(FirstChars::any().contains('ф'));
+        assert!(!FirstChars::none().is_nullable());
+    }
+
+    #[test]
+    fn first_chars_of_patterns() {
+        assert_eq!("let".first_chars(), FirstChars::char('l'));
+        assert_eq!("".first_chars(), FirstChars::empty());
+        assert_eq!(('a'..'c').first_chars(), FirstChars::range('a'..='b'));
+        assert!(!('a'..'a').first_chars().contains('a'));
+
+        let pattern = (pat('-') * ..=1) & cap(pat('0'..='9') * (1..));
+        let set = pattern.first_chars();
+        assert!(set.contains('-'));
+        assert!(set.contains('7'));
+        assert!(!set.contains('+'));
+        assert!(!set.is_nullable());
+
+        let pattern = pat("/*") & pat(ANY).until("*/");
+        assert_eq!(pattern.first_chars(), FirstChars::char('/'));
+
+        let pattern = pat("a") | (pat('b') * 2) | (pat('c') * ..);
+        let set = pattern.first_chars();
+        assert!(set.contains('a') && set.contains('b') && set.contains('c'));
+        assert!(set.is_nullable());
+
+        let set = (pat(char::is_whitespace) * (1..)).first_chars();
+        assert!(set.contains('\t'));
+        assert!(!set.is_ascii());
+    }
+}
@@ -0,0 +1,283 @@
+use crate::parse::{ParseIterator, ParseResult, ParseWith};
+use crate::span::{Position, Span};
+use std::ops::Range;
+
+/// Edit of the text, which replaces the byte `range` by the `text`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edit<'a> {
+    pub range: Range<usize>,
+    pub text: &'a str,
+}
+
+impl<'a> Edit<'a> {
+    pub fn new(range: Range<usize>, text: &'a str) -> Self {
+        Edit { range, text }
+    }
+
+    /// Applies the edit to the old text
+    pub fn apply(&self, text: &mut String) {
+        text.replace_range(self.range.clone(), self.text);
+    }
+
+    /// End of the replaced text in the new text
+    fn new_end(&self) -> usize {
+        self.range.start + self.text.len()
+    }
+}
+
+impl<'p, 't, P, C> ParseIterator<'p, 't, P, C>
+where
+    P: ParseWith<'t, C>,
+{
+    /// Updates `tokens` of the old text after the `edit`
+    ///
+    /// The iterator must be made for the new text. It re-lexes from the end
+    /// of the last token which lexemes didn't look ahead into the edit from,
+    /// until a new token starts or ends where an old one starts, then the rest
+    /// of old tokens is kept with shifted spans.
+    /// Returns the range of new tokens in `tokens`.
+    ///
+    /// Tokens don't keep the stack of lexer modes, so parsers with modes
+    /// aren't re-lexed, and `None` is returned with `tokens` unchanged.
+    pub fn relex(
+        self,
+        tokens: &mut Vec<ParseResult<P::Token>>,
+        edit: &Edit,
+    ) -> Option<Range<usize>> {
+        if self.parser.has_modes() {
+            return None;
+        }
+
+        // The text before the edit is the same, so lookahead is read in the new one
+        let (parser, text) = (self.parser, self.text);
+        let first = tokens
+            .iter()
+            .position(|tok| {
+                let span = tok.span();
+                span.end.offset >= edit.range.start
+                    || span.start.offset + parser.read_furthest(&text[span.start.offset..], 0).len
+                        >= edit.range.start
+            })
+            .unwrap_or(tokens.len());
+
+        // Skipped lexemes leave gaps, so the gap before the token is lexed too
+        let start = match first {
+            0 => Position::default(),
+            first => tokens[first - 1].span().end,
+        };
+
+        // Finds the old token which starts at the same text after the edit
+        let find_old = |new: Position| {
+            if new.offset < edit.new_end() {
+                return None;
+            }
+
+            let old = new.offset - edit.new_end() + edit.range.end;
+            tokens[first..]
+                .iter()
+                .position(|tok| tok.span().start.offset >= old)
+                .map(|idx| first + idx)
+                .filter(|&idx| tokens[idx].span().start.offset == old)
+        };
+
+        let mut new_tokens = Vec::new();
+        let mut sync = None;
+        for tok in self.start_at(start) {
+            let Span { start, end } = tok.span();
+            if matches!(tok, ParseResult::Error(_)) {
+                new_tokens.push(tok);
+                continue;
+            }
+
+            // Skipped lexemes leave gaps, so tokens may sync at their start
+            if let Some(idx) = find_old(start) {
+                sync = Some((idx, start));
+                break;
+            }
+
+            new_tokens.push(tok);
+            if let Some(idx) = find_old(end) {
+                sync = Some((idx, end));
+                break;
+            }
+        }
+
+        let last = match sync {
+            Some((idx, new_start)) => {
+                let old_start = tokens[idx].span().start;
+                for tok in &mut tokens[idx..] {
+                    shift(tok, old_start, new_start);
+                }
+
+                idx
+            }
+            None => tokens.len(),
+        };
+
+        let len = new_tokens.len();
+        tokens.splice(first..last, new_tokens);
+        Some(first..first + len)
+    }
+}
+
+/// Moves the position after `old` to the same place after `new`
+fn shift_position(pos: Position, old: Position, new: Position) -> Position {
+    let mut shifted = Position {
+        offset: pos.offset - old.offset + new.offset,
+        line: pos.line - old.line + new.line,
+        column: pos.column,
+    };
+
+    // Only columns on the line of the shifted text change
+    if pos.line == old.line {
+        shifted.column.chars = pos.column.chars - old.column.chars + new.column.chars;
+        shifted.column.utf16 = pos.column.utf16 - old.column.utf16 + new.column.utf16;
+        shifted.column.graphemes =
+            pos.column.graphemes - old.column.graphemes + new.column.graphemes;
+    }
+
+    shifted
+}
+
+fn shift<T>(tok: &mut ParseResult<T>, old: Position, new: Position) {
+    match tok {
+        ParseResult::Ok(_, span) | ParseResult::Invalid(_, span) | ParseResult::Skipped(span) => {
+            span.start = shift_position(span.start, old, new);
+            span.end = shift_position(span.end, old, new);
+        }
+        ParseResult::Error(error) => {
+            error.position = shift_position(error.position, old, new);
+            error.furthest = shift_position(error.furthest, old, new);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{lex, skip};
+    use crate::modes::Modes;
+    use crate::parse::Parse;
+    use crate::patterns::{pat, ANY};
+
+    #[derive(Copy, Clone, PartialEq, Debug, ReadToken)]
+    enum Token {
+        Name,
+        Number,
+        Comment,
+        Space,
+        Eq,
+        A,
+        B,
+        C,
+        D,
+        X,
+    }
+
+    #[test]
+    fn relex() {
+        let name = pat('a'..='z') * (1..);
+        let number = pat('0'..='9') * (1..);
+        let comment = pat("/*") & pat(ANY).until("*/");
+        let lx = lex(name, Token::Name)
+            | lex(number, Token::Number)
+            | lex(comment, Token::Comment)
+            | lex(char::is_whitespace, Token::Space)
+            | lex('=', Token::Eq);
+
+        let cases = [
+            ("ab = 10\nc = 2", Edit::new(5..7, "1234"), 2),
+            ("ab = 10\nc = 2", Edit::new(2..2, "c"), 1),
+            ("ab = 10\nc = 2", Edit::new(0..0, "x "), 2),
+            ("ab = 10\nc = 2", Edit::new(4..8, ""), 1),
+            ("ab = 10\nc = 2", Edit::new(2..3, "\n\n"), 3),
+            ("ab = 10\nc = 2", Edit::new(13..13, "3 /*"), 3),
+            ("ab /* x */ = 1", Edit::new(4..4, "*/ c /*"), 1),
+            ("ab /* x */ = 1", Edit::new(3..3, "/**/ c "), 5),
+            ("", Edit::new(0..0, "a=1"), 3),
+        ];
+
+        for (old, edit, changed) in cases.iter().cloned() {
+            let mut text = String::from(old);
+            edit.apply(&mut text);
+
+            let mut tokens: Vec<_> = lx.tokenize(old).collect();
+            let range = lx.tokenize(&text).relex(&mut tokens, &edit).unwrap();
+            let expected: Vec<_> = lx.tokenize(&text).collect();
+
+            assert_eq!(tokens, expected, "{:?} {:?}", old, edit);
+            assert_eq!(range.len(), changed, "{:?} {:?}", old, edit);
+        }
+    }
+
+    #[test]
+    fn relex_skip() {
+        let name = pat('a'..='z') * (1..);
+        let lx =
+            skip(pat(char::is_whitespace) * (1..)) | lex(name, Token::Name) | lex('=', Token::Eq);
+
+        let cases = [
+            ("ab = cd\nef = gh", Edit::new(5..5, "x"), 1),
+            ("ab = cd\nef = gh", Edit::new(4..5, ""), 1),
+            ("ab = cd\nef = gh", Edit::new(7..8, " = "), 2),
+        ];
+
+        for (old, edit, changed) in cases.iter().cloned() {
+            let mut text = String::from(old);
+            edit.apply(&mut text);
+
+            let mut tokens: Vec<_> = lx.tokenize(old).collect();
+            let range = lx.tokenize(&text).relex(&mut tokens, &edit).unwrap();
+            let expected: Vec<_> = lx.tokenize(&text).collect();
+
+            assert_eq!(tokens, expected, "{:?} {:?}", old, edit);
+            assert_eq!(range.len(), changed, "{:?} {:?}", old, edit);
+        }
+    }
+
+    #[test]
+    fn relex_lookahead() {
+        let comment = pat("/*") & pat(ANY).until("*/");
+        let lx = lex("abcd", Token::X)
+            | lex('a', Token::A)
+            | lex('b', Token::B)
+            | lex('c', Token::C)
+            | lex('d', Token::D)
+            | lex(comment, Token::Comment)
+            | lex(' ', Token::Space);
+
+        let cases = [
+            ("abc", Edit::new(3..3, "d"), 1),
+            ("abcd", Edit::new(3..4, ""), 3),
+            ("cabc", Edit::new(4..4, "d"), 1),
+            ("ab /* x", Edit::new(7..7, " */"), 1),
+        ];
+
+        for (old, edit, changed) in cases.iter().cloned() {
+            let mut text = String::from(old);
+            edit.apply(&mut text);
+
+            let mut tokens: Vec<_> = lx.tokenize(old).collect();
+            let range = lx.tokenize(&text).relex(&mut tokens, &edit).unwrap();
+            let expected: Vec<_> = lx.tokenize(&text).collect();
+
+            assert_eq!(tokens, expected, "{:?} {:?}", old, edit);
+            assert_eq!(range.len(), changed, "{:?} {:?}", old, edit);
+        }
+
+        let text = "abcd";
+        let tokens: Vec<_> = lx.tokenize(text).collect();
+        assert_eq!(tokens, [ParseResult::Ok(Token::X, Span::of(text, 0..4))]);
+    }
+
+    #[test]
+    fn relex_modes() {
+        let modes = Modes::new(lex('a', Token::A).push(1)).mode(lex('b', Token::B).pop());
+        let mut tokens: Vec<_> = modes.tokenize("ab").collect();
+        let old = tokens.clone();
+
+        let edit = Edit::new(2..2, "a");
+        assert_eq!(modes.tokenize("aba").relex(&mut tokens, &edit), None);
+        assert_eq!(tokens, old);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::span::{Column, Position};
+use std::fmt;
+use std::ops::{Index, RangeFrom};
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+
+/// Input of lexers, which is text in `str` or binary data in `[u8]`
+///
+/// Lexers, their iterator and errors are generic over the input,
+/// so modes, skipped lexemes and the error recovery work for bytes as well.
+///
+pub trait Input: Index<RangeFrom<usize>, Output = Self> {
+    /// Char of text or byte of bytes, which errors report
+    type Unit: Unit;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the unit at the byte `offset`, `None` at the end of the input
+    fn unit_at(&self, offset: usize) -> Option<Self::Unit>;
+
+    /// Checks if the byte `offset` is at the start of a unit
+    fn is_boundary(&self, offset: usize) -> bool;
+
+    /// Moves the `position` `len` bytes forward in the input
+    ///
+    /// The input is the whole input, which the position points into,
+    /// so only the passed part of it is scanned.
+    fn advance(&self, position: Position, len: usize) -> Position;
+}
+
+/// Unit of an input, which errors report as unexpected
+pub trait Unit: Copy + fmt::Debug + PartialEq {
+    /// Writes the unit into an error message, like `'x'` or `byte 0x89`
+    fn describe(&self, f: &mut fmt::Formatter) -> fmt::Result;
+}
+
+impl Unit for char {
+    fn describe(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Unit for u8 {
+    fn describe(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "byte {:#04x}", self)
+    }
+}
+
+impl Input for str {
+    type Unit = char;
+
+    fn len(&self) -> usize {
+        str::len(self)
+    }
+
+    fn unit_at(&self, offset: usize) -> Option<char> {
+        self[offset..].chars().next()
+    }
+
+    fn is_boundary(&self, offset: usize) -> bool {
+        self.is_char_boundary(offset)
+    }
+
+    fn advance(&self, position: Position, len: usize) -> Position {
+        let passed = &self[position.offset..position.offset + len];
+        let offset = position.offset + len;
+
+        match passed.rfind('\n') {
+            Some(nl) => {
+                let tail = &passed[nl + 1..];
+                Position {
+                    offset,
+                    line: position.line + passed.matches('\n').count(),
+                    column: Column {
+                        chars: tail.chars().count(),
+                        utf16: tail.encode_utf16().count(),
+                        graphemes: tail.graphemes(true).count(),
+                    },
+                }
+            }
+            None => {
+                // The passed text may continue a grapheme cluster
+                let mut graphemes = passed.graphemes(true).count();
+                if !passed.is_empty() && !is_grapheme_boundary(self, position.offset) {
+                    graphemes -= 1;
+                }
+
+                Position {
+                    offset,
+                    line: position.line,
+                    column: Column {
+                        chars: position.column.chars + passed.chars().count(),
+                        utf16: position.column.utf16 + passed.encode_utf16().count(),
+                        graphemes: position.column.graphemes + graphemes,
+                    },
+                }
+            }
+        }
+    }
+}
+
+fn is_grapheme_boundary(text: &str, offset: usize) -> bool {
+    GraphemeCursor::new(offset, text.len(), true)
+        .is_boundary(text, 0)
+        .unwrap_or(true)
+}
+
+/// Lines of bytes are separated by `b'\n'` too, and all columns count bytes
+impl Input for [u8] {
+    type Unit = u8;
+
+    fn len(&self) -> usize {
+        <[u8]>::len(self)
+    }
+
+    fn unit_at(&self, offset: usize) -> Option<u8> {
+        self.get(offset).copied()
+    }
+
+    fn is_boundary(&self, offset: usize) -> bool {
+        offset <= self.len()
+    }
+
+    fn advance(&self, position: Position, len: usize) -> Position {
+        let passed = &self[position.offset..position.offset + len];
+        let (line, column) = match passed.iter().rposition(|&b| b == b'\n') {
+            Some(nl) => {
+                let lines = passed.iter().filter(|&&b| b == b'\n').count();
+                (position.line + lines, len - nl - 1)
+            }
+            None => (position.line, position.column.chars + len),
+        };
+
+        Position {
+            offset: position.offset + len,
+            line,
+            column: Column {
+                chars: column,
+                utf16: column,
+                graphemes: column,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_position() {
+        let bytes: &[u8] = b"ab\n\xff\xfe\nx";
+        let pos = Position::at(bytes, 2);
+        assert_eq!((pos.line, pos.column.chars), (0, 2));
+
+        let pos = pos.advance(bytes, 3);
+        assert_eq!((pos.offset, pos.line, pos.column.chars), (5, 1, 2));
+        assert_eq!(pos.column.graphemes, 2);
+
+        let pos = pos.advance(bytes, 2);
+        assert_eq!((pos.line, pos.column.chars), (2, 1));
+        assert_eq!(pos, Position::at(bytes, bytes.len()));
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!("фx".unit_at(0), Some('ф'));
+        assert_eq!("фx".unit_at(2), Some('x'));
+        assert!(!"фx".is_boundary(1));
+        assert_eq!(b"\x89".unit_at(0), Some(0x89));
+        assert_eq!(b"\x89".unit_at(1), None);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::captures::Captures;
+use crate::read_token::WithContext;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ops::Index;
+use std::rc::Rc;
+
+/// Interned string, which is an index in the `Interner`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(pub u32);
+
+/// Storage of strings where equal strings have the same symbol
+///
+/// Tokens with symbols don't borrow the text, so they outlive it
+/// without allocating a string for every token. Each string is stored once
+/// and shared by the map of symbols and the list of strings.
+///
+#[derive(Clone, Debug, Default)]
+pub struct Interner {
+    symbols: HashMap<Rc<str>, Symbol>,
+    strings: Vec<Rc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    /// Returns the symbol of the string, adding the string if it's new
+    pub fn intern(&mut self, string: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(string) {
+            return symbol;
+        }
+
+        let idx = u32::try_from(self.strings.len()).expect("too many interned strings");
+        let symbol = Symbol(idx);
+        let string: Rc<str> = string.into();
+        self.symbols.insert(string.clone(), symbol);
+        self.strings.push(string);
+        symbol
+    }
+
+    /// Returns the symbol of the string if it was interned
+    pub fn get(&self, string: &str) -> Option<Symbol> {
+        self.symbols.get(string).copied()
+    }
+
+    /// Returns the string of the symbol
+    pub fn resolve(&self, symbol: Symbol) -> Option<&str> {
+        self.strings.get(symbol.0 as usize).map(|string| &**string)
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+impl Index<Symbol> for Interner {
+    type Output = str;
+
+    fn index(&self, symbol: Symbol) -> &Self::Output {
+        &self.strings[symbol.0 as usize]
+    }
+}
+
+/// Makes a token constructor which interns the matched text
+///
+/// For example, `lex(name, intern(Token::Name))` makes a lexeme
+/// which needs the `Interner` passed by `ParseIterator::context`,
+/// so a lexer with it doesn't tokenize without the interner.
+///
+pub fn intern<F, T>(token: F) -> WithContext<impl Fn(&str, Captures, &mut Interner) -> T, Interner>
+where
+    F: Fn(Symbol) -> T,
+{
+    WithContext::new(move |text: &str, _: Captures, interner: &mut Interner| {
+        token(interner.intern(text))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+    use crate::parse::{Parse, ParseResult};
+    use crate::patterns::pat;
+
+    #[test]
+    fn interner() {
+        let mut interner = Interner::new();
+        assert!(interner.is_empty());
+
+        let a = interner.intern("a");
+        let b = interner.intern("b");
+        assert_ne!(a, b);
+        assert_eq!(interner.intern("a"), a);
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.get("b"), Some(b));
+        assert_eq!(interner.get("c"), None);
+        assert_eq!(interner.resolve(b), Some("b"));
+        assert_eq!(interner.resolve(Symbol(2)), None);
+        assert_eq!(&interner[a], "a");
+    }
+
+    #[derive(Copy, Clone, PartialEq, Debug, ReadToken)]
+    enum Token {
+        Name(Symbol),
+        Space,
+    }
+
+    #[test]
+    fn intern_tokens() {
+        let name = pat('a'..='z') * (1..);
+        let lx = lex(name, intern(Token::Name)) | lex(' ', Token::Space);
+
+        let mut interner = Interner::new();
+        let text = String::from("ab cd ab");
+        let tokens: Vec<_> = lx
+            .tokenize(&text)
+            .context(&mut interner)
+            .filter_map(|r| match r {
+                ParseResult::Ok(Token::Name(symbol), _) => Some(symbol),
+                ParseResult::Ok(Token::Space, _) => None,
+                _ => unreachable!(),
+            })
+            .collect();
+
+        drop(text);
+        assert_eq!(tokens, [Symbol(0), Symbol(1), Symbol(0)]);
+        assert_eq!(&interner[tokens[1]], "cd");
+    }
+}
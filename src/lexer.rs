@@ -1,44 +1,283 @@
-use super::parse::Parse;
+use super::captures::Captures;
+use super::dfa::Compiled;
+use super::dispatch::Dispatch;
+use super::error::{Furthest, TokenError};
+use super::first_chars::FirstChars;
+use super::input::Input;
+use super::modes::Action;
+use super::parse::{Lexed, Parse, ParseWith};
 use super::read_pattern::ReadPattern;
-use super::read_token::ReadToken;
+use super::read_token::{TryReadToken, TryReadTokenWith};
+use super::regex::Regex;
+use std::marker::PhantomData;
 use std::ops::BitOr;
 
 pub struct Lexeme<P, R> {
-    read_pattern: P,
-    read_token: R,
+    pub(crate) read_pattern: P,
+    pub(crate) read_token: R,
+    pub(crate) priority: i32,
+    pub(crate) action: Action,
+    pub(crate) name: Option<&'static str>,
+}
+
+impl<P, R> Lexeme<P, R> {
+    /// Sets the priority of the lexeme
+    ///
+    /// In the longest match mode a lexeme with a higher priority
+    /// wins over others which match the same length.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Sets the name of the lexeme to report errors
+    pub fn named(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Enters the `mode` after the lexeme is matched
+    pub fn push(mut self, mode: usize) -> Self {
+        self.action = Action::Push(mode);
+        self
+    }
+
+    /// Returns to the previous mode after the lexeme is matched
+    pub fn pop(mut self) -> Self {
+        self.action = Action::Pop;
+        self
+    }
+
+    /// Replaces the current mode after the lexeme is matched
+    pub fn switch(mut self, mode: usize) -> Self {
+        self.action = Action::Switch(mode);
+        self
+    }
+
+    /// Skips the matched text, so the lexeme makes no token
+    ///
+    /// The token type of the lexeme is of no use, so it's `()` usually,
+    /// like `lex(' ', ()).skip()`.
+    pub fn skip<T>(self) -> Skip<Self, T> {
+        Skip(self, PhantomData)
+    }
 }
 
 pub fn lex<'t, P, R>(read_pattern: P, read_token: R) -> Lexeme<P, R>
 where
     P: ReadPattern,
-    R: ReadToken<'t>,
+    R: TryReadToken<'t>,
 {
     Lexeme {
         read_pattern,
         read_token,
+        priority: 0,
+        action: Action::None,
+        name: None,
+    }
+}
+
+impl<'t, P, R> Lexeme<P, R>
+where
+    P: ReadPattern,
+    R: TryReadToken<'t>,
+{
+    fn read<C>(
+        &self,
+        text: &'t str,
+        pos: usize,
+        ctx: &mut C,
+    ) -> Option<(Result<R::Token, TokenError>, usize)>
+    where
+        R: TryReadTokenWith<'t, C>,
+    {
+        let mut caps = Captures::with_offset(text, pos);
+        let len = self.read_pattern.read_captures(text, &mut caps)?;
+        caps.set_len(len);
+        let tok = self.read_token.try_read_token_with(&text[..len], caps, ctx);
+        Some((tok, len))
     }
 }
 
 impl<'t, P, R> Parse<'t> for Lexeme<P, R>
 where
     P: ReadPattern,
-    R: ReadToken<'t>,
+    R: TryReadToken<'t>,
 {
     type Token = R::Token;
 
-    fn parse(&self, text: &'t str) -> Option<(Self::Token, usize)> {
-        let len = self.read_pattern.read_pattern(text)?;
-        let tok = self.read_token.read_token(&text[..len]);
-        Some((tok, len))
+    fn read_furthest(&self, text: &'t str, _mode: usize) -> Furthest {
+        Furthest::new(self.read_pattern.read_furthest(text), self.name)
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    fn action(&self) -> Action {
+        self.action
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn read_len(&self, text: &'t str, _mode: usize) -> Option<usize> {
+        self.read_pattern.read_pattern(text)
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        self.read_pattern.first_chars()
+    }
+
+    fn to_regex(&self) -> Option<Regex> {
+        self.read_pattern.to_regex()
     }
 }
 
-impl<'t, P, T, R> BitOr<R> for Lexeme<P, T>
+impl<'t, C, P, R> ParseWith<'t, C> for Lexeme<P, R>
 where
     P: ReadPattern,
-    T: ReadToken<'t>,
-    R: Parse<'t>,
+    R: TryReadTokenWith<'t, C>,
 {
+    fn parse_exact(
+        &self,
+        text: &'t str,
+        len: usize,
+        pos: usize,
+        _mode: usize,
+        ctx: &mut C,
+    ) -> Option<Result<Option<Self::Token>, TokenError>> {
+        // The length is already matched, so only captures need the pattern
+        let mut caps = Captures::with_offset(text, pos);
+        if self.read_pattern.has_captures()
+            && !self.read_pattern.read_captures_exact(text, len, &mut caps)
+        {
+            return None;
+        }
+
+        caps.set_len(len);
+        let tok = self.read_token.try_read_token_with(&text[..len], caps, ctx);
+        Some(tok.map(Some))
+    }
+
+    fn parse_action(
+        &self,
+        text: &'t str,
+        pos: usize,
+        _mode: usize,
+        ctx: &mut C,
+    ) -> Option<Lexed<Self::Token>> {
+        let (tok, len) = self.read(text, pos, ctx)?;
+        Some((tok.map(Some), len, self.action))
+    }
+}
+
+/// Lexeme which is matched like others, but makes no token
+///
+/// `ParseIterator` consumes the text without producing an item,
+/// and `parse` doesn't match it. The empty match isn't a match,
+/// so next lexemes are tried instead.
+///
+pub struct Skip<L, T>(L, PhantomData<fn() -> T>);
+
+/// Skips the text or bytes matched by the `pattern`
+pub fn skip<P, T>(read_pattern: P) -> Skip<Lexeme<P, ()>, T> {
+    Lexeme {
+        read_pattern,
+        read_token: (),
+        priority: 0,
+        action: Action::None,
+        name: None,
+    }
+    .skip()
+}
+
+impl<'t, I, L, T> Parse<'t, I> for Skip<L, T>
+where
+    I: Input + ?Sized,
+    L: Parse<'t, I>,
+{
+    type Token = T;
+
+    fn read_furthest(&self, text: &'t I, mode: usize) -> Furthest {
+        self.0.read_furthest(text, mode)
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.0.name()
+    }
+
+    fn action(&self) -> Action {
+        self.0.action()
+    }
+
+    fn priority(&self) -> i32 {
+        self.0.priority()
+    }
+
+    fn has_modes(&self) -> bool {
+        self.0.has_modes()
+    }
+
+    fn read_len(&self, text: &'t I, mode: usize) -> Option<usize> {
+        self.0.read_len(text, mode).filter(|&len| len != 0)
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        self.0.first_chars()
+    }
+
+    fn to_regex(&self) -> Option<Regex> {
+        self.0.to_regex()
+    }
+}
+
+impl<'t, C, I, L, T> ParseWith<'t, C, I> for Skip<L, T>
+where
+    I: Input + ?Sized,
+    L: ParseWith<'t, C, I>,
+{
+    fn parse_exact(
+        &self,
+        _text: &'t I,
+        len: usize,
+        _pos: usize,
+        _mode: usize,
+        _ctx: &mut C,
+    ) -> Option<Result<Option<Self::Token>, TokenError>> {
+        // The length is already matched, and the skipped token isn't made
+        if len == 0 {
+            return None;
+        }
+
+        Some(Ok(None))
+    }
+
+    fn parse_action(
+        &self,
+        text: &'t I,
+        _pos: usize,
+        mode: usize,
+        _ctx: &mut C,
+    ) -> Option<Lexed<Self::Token>> {
+        let len = self.read_len(text, mode)?;
+        Some((Ok(None), len, self.0.action()))
+    }
+}
+
+impl<L, T, R> BitOr<R> for Skip<L, T> {
+    type Output = Lexer<Skip<L, T>, R>;
+
+    fn bitor(self, rhs: R) -> Self::Output {
+        Lexer {
+            left: self,
+            right: rhs,
+        }
+    }
+}
+
+impl<P, T, R> BitOr<R> for Lexeme<P, T> {
     type Output = Lexer<Lexeme<P, T>, R>;
 
     fn bitor(self, rhs: R) -> Self::Output {
@@ -59,22 +298,76 @@ pub struct Lexer<L, R> {
     right: R,
 }
 
-impl<'t, T, L, R> Parse<'t> for Lexer<L, R>
+impl<L, R> Lexer<L, R> {
+    /// Switches the lexer to the longest match mode
+    pub fn longest(self) -> Longest<Self> {
+        Longest(self)
+    }
+
+    /// Builds a dispatch table by the first character of lexemes
+    pub fn dispatch<'t, C>(&self) -> Dispatch<'_, 't, <Self as Parse<'t>>::Token, C>
+    where
+        Self: ParseWith<'t, C>,
+    {
+        Dispatch::new(self)
+    }
+}
+
+impl<'t, I, T, L, R> Parse<'t, I> for Lexer<L, R>
 where
-    L: Parse<'t, Token = T>,
-    R: Parse<'t, Token = T>,
+    I: Input + ?Sized,
+    L: Parse<'t, I, Token = T>,
+    R: Parse<'t, I, Token = T>,
 {
     type Token = T;
 
-    fn parse(&self, text: &'t str) -> Option<(Self::Token, usize)> {
-        self.left.parse(text).or_else(|| self.right.parse(text))
+    fn read_len(&self, text: &'t I, mode: usize) -> Option<usize> {
+        self.left
+            .read_len(text, mode)
+            .or_else(|| self.right.read_len(text, mode))
+    }
+
+    fn read_furthest(&self, text: &'t I, mode: usize) -> Furthest {
+        let left = self.left.read_furthest(text, mode);
+        left.merge(self.right.read_furthest(text, mode))
+    }
+
+    fn has_modes(&self) -> bool {
+        self.left.has_modes() || self.right.has_modes()
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        self.left.first_chars().union(self.right.first_chars())
+    }
+
+    fn for_each_lexeme<'p>(&'p self, f: &mut dyn FnMut(&'p dyn Parse<'t, I, Token = Self::Token>)) {
+        self.left.for_each_lexeme(f);
+        self.right.for_each_lexeme(f);
     }
 }
 
-impl<'t, L, R, P> BitOr<P> for Lexer<L, R>
+impl<'t, C, I, T, L, R> ParseWith<'t, C, I> for Lexer<L, R>
 where
-    P: Parse<'t>,
+    I: Input + ?Sized,
+    L: ParseWith<'t, C, I, Token = T>,
+    R: ParseWith<'t, C, I, Token = T>,
 {
+    fn parse_action(&self, text: &'t I, pos: usize, mode: usize, ctx: &mut C) -> Option<Lexed<T>> {
+        self.left
+            .parse_action(text, pos, mode, ctx)
+            .or_else(|| self.right.parse_action(text, pos, mode, ctx))
+    }
+
+    fn for_each_lexeme_with<'p>(
+        &'p self,
+        f: &mut dyn FnMut(&'p dyn ParseWith<'t, C, I, Token = Self::Token>),
+    ) {
+        self.left.for_each_lexeme_with(f);
+        self.right.for_each_lexeme_with(f);
+    }
+}
+
+impl<L, R, P> BitOr<P> for Lexer<L, R> {
     type Output = Lexer<Lexer<L, R>, P>;
 
     fn bitor(self, rhs: P) -> Self::Output {
@@ -85,9 +378,117 @@ where
     }
 }
 
+/// Lexer in the longest match mode
+///
+/// Unlike `Lexer`, which takes the first matching lexeme,
+/// this tries every lexeme and keeps the longest match.
+/// Matches of the same length are resolved by the priority of lexemes
+/// and then by the declaration order.
+///
+pub struct Longest<P>(P);
+
+impl<P> Longest<P> {
+    /// Builds a dispatch table by the first character of lexemes
+    pub fn dispatch<'t, C>(&self) -> Dispatch<'_, 't, P::Token, C>
+    where
+        P: ParseWith<'t, C>,
+    {
+        Dispatch::new(&self.0).longest()
+    }
+
+    /// Compiles lexemes into one automaton
+    pub fn compile<'t, C>(&self) -> Compiled<'_, 't, P::Token, C>
+    where
+        P: ParseWith<'t, C>,
+    {
+        Compiled::new(&self.0)
+    }
+}
+
+impl<P> Longest<P> {
+    /// Finds the lexeme with the longest match
+    fn find<'p, 't, C, I>(
+        &'p self,
+        text: &'t I,
+        mode: usize,
+    ) -> Option<&'p dyn ParseWith<'t, C, I, Token = P::Token>>
+    where
+        I: Input + ?Sized,
+        P: ParseWith<'t, C, I>,
+    {
+        let mut best = None;
+        let mut best_key = None;
+        self.0.for_each_lexeme_with(&mut |lexeme| {
+            if let Some(len) = lexeme.read_len(text, mode) {
+                let key = Some((len, lexeme.priority()));
+                if key > best_key {
+                    best = Some(lexeme);
+                    best_key = key;
+                }
+            }
+        });
+
+        best
+    }
+}
+
+impl<'t, I, P> Parse<'t, I> for Longest<P>
+where
+    I: Input + ?Sized,
+    P: Parse<'t, I>,
+{
+    type Token = P::Token;
+
+    fn read_furthest(&self, text: &'t I, mode: usize) -> Furthest {
+        let mut furthest = Furthest::default();
+        self.0.for_each_lexeme(&mut |lexeme| {
+            furthest = std::mem::take(&mut furthest).merge(lexeme.read_furthest(text, mode));
+        });
+
+        furthest
+    }
+
+    fn read_len(&self, text: &'t I, mode: usize) -> Option<usize> {
+        let mut best = None;
+        self.0.for_each_lexeme(&mut |lexeme| {
+            if let Some(len) = lexeme.read_len(text, mode) {
+                best = best.max(Some(len));
+            }
+        });
+
+        best
+    }
+
+    fn has_modes(&self) -> bool {
+        self.0.has_modes()
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        self.0.first_chars()
+    }
+}
+
+impl<'t, C, I, P> ParseWith<'t, C, I> for Longest<P>
+where
+    I: Input + ?Sized,
+    P: ParseWith<'t, C, I>,
+{
+    fn parse_action(
+        &self,
+        text: &'t I,
+        pos: usize,
+        mode: usize,
+        ctx: &mut C,
+    ) -> Option<Lexed<Self::Token>> {
+        self.find(text, mode)?.parse_action(text, pos, mode, ctx)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parse::ParseResult;
+    use crate::patterns::{cap, cap_named, pat, ANY};
 
     #[derive(Copy, Clone, PartialEq, Debug, ReadToken)]
     enum Token {
@@ -98,6 +499,9 @@ mod tests {
         Num,
         Eq,
         Semicolon,
+        Hex(u32),
+        Var,
+        Let,
     }
 
     #[test]
@@ -125,4 +529,199 @@ mod tests {
         assert_eq!(l.parse(";").unwrap(), (Token::Semicolon, 1));
         assert!(l.parse("!").is_none());
     }
+
+    #[test]
+    fn longest_match() {
+        let l = (lex('+', Token::Plus) | lex("+=", Token::PlusEq)).longest();
+        assert_eq!(l.parse("+=").unwrap(), (Token::PlusEq, 2));
+        assert_eq!(l.parse("+").unwrap(), (Token::Plus, 1));
+        assert_eq!(l.read_len("+=1", 0), Some(2));
+        assert!(l.parse("=").is_none());
+
+        let name = pat('a'..='z') * (1..);
+        let l = (lex("let", Token::Let) | lex(name, Token::Name)).longest();
+        assert_eq!(l.parse("letter").unwrap(), (Token::Name, 6));
+        assert_eq!(l.parse("let x").unwrap(), (Token::Let, 3));
+
+        // The first declared lexeme wins a tie
+        let name = pat('a'..='z') * (1..);
+        let l = (lex(name, Token::Name) | lex("let", Token::Let)).longest();
+        assert_eq!(l.parse("let").unwrap(), (Token::Name, 3));
+    }
+
+    #[test]
+    fn longest_match_priority() {
+        let name = || pat('a'..='z') * (1..);
+        let keywords = lex("let", Token::Let).priority(10) | lex("x", Token::Var).priority(10);
+        let l = (lex(name(), Token::Name) | keywords).longest();
+        assert_eq!(l.parse("let").unwrap(), (Token::Let, 3));
+        assert_eq!(l.parse("x").unwrap(), (Token::Var, 1));
+        assert_eq!(l.parse("letter").unwrap(), (Token::Name, 6));
+
+        // The priority is lower than the default one
+        let l = (lex(name(), Token::Name).priority(-1) | lex("let", Token::Let)).longest();
+        assert_eq!(l.parse("let").unwrap(), (Token::Let, 3));
+        assert_eq!(l.parse("le").unwrap(), (Token::Name, 2));
+    }
+
+    #[test]
+    fn lexeme_caps() {
+        let hex = pat('0'..='9') | ('a'..='f');
+        let lx = lex(pat("0x") & cap(hex * (1..)), |_, caps: Captures| {
+            Token::Hex(u32::from_str_radix(&caps[0], 16).unwrap())
+        });
+        assert_eq!(lx.parse("0x1f").unwrap(), (Token::Hex(31), 4));
+
+        let var = pat('$') & cap_named("var", pat('a'..='z') * (1..));
+        let lx = lex(var, |_, caps: Captures| match caps.get("var") {
+            Some("name") => Token::Name,
+            _ => Token::Var,
+        });
+        assert_eq!(lx.parse("$name").unwrap(), (Token::Name, 5));
+        assert_eq!(lx.parse("$x").unwrap(), (Token::Var, 2));
+    }
+
+    #[test]
+    fn lex_error() {
+        let name = pat('a'..='z') * (1..);
+        let hex = pat("0x") & ((pat('0'..='9') | ('a'..='f')) * (1..));
+        let comment = pat("/*") & pat(ANY).until("*/");
+        let lx = lex(name, Token::Name).named("name")
+            | lex(hex, |_, _| Token::Hex(0)).named("hex number")
+            | lex(comment, Token::Star).named("comment")
+            | lex(' ', Token::Semicolon)
+            | lex('=', Token::Eq);
+
+        let code = "let xy = 0x1 /* comment";
+        let error = match lx.tokenize(code).last() {
+            Some(ParseResult::Error(error)) => error,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(error.position.offset, 13);
+        assert_eq!(error.found, Some('/'));
+        assert_eq!(error.furthest.offset, code.len());
+        assert_eq!(error.expected, ["comment"]);
+        assert_eq!(error.to_string(), "unterminated comment starting at 13");
+
+        let code = "x = 0xz";
+        let error = match lx.tokenize(code).last() {
+            Some(ParseResult::Error(error)) => error,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(error.expected, ["hex number"]);
+        assert_eq!(
+            error.to_string(),
+            "invalid hex number starting at 4: unexpected 'z' at 6"
+        );
+
+        let error = match lx.tokenize("x;").last() {
+            Some(ParseResult::Error(error)) => error,
+            _ => unreachable!(),
+        };
+
+        assert!(error.expected.is_empty());
+        assert_eq!(error.to_string(), "unexpected ';' at 1");
+    }
+
+    #[test]
+    fn skip_lexemes() {
+        use crate::span::Span;
+
+        let name = pat('a'..='z') * (1..);
+        let comment = pat("/*") & pat(ANY).until("*/");
+        let lx = skip(pat(' ') * (1..))
+            | lex(comment, ()).named("comment").skip()
+            | lex(name, Token::Name)
+            | lex('=', Token::Eq);
+
+        let code = "  x /* c */= y  ";
+        let expected = [
+            ParseResult::Ok(Token::Name, Span::of(code, 2..3)),
+            ParseResult::Ok(Token::Eq, Span::of(code, 11..12)),
+            ParseResult::Ok(Token::Name, Span::of(code, 13..14)),
+        ];
+
+        let tokens: Vec<_> = lx.tokenize(code).collect();
+        assert_eq!(tokens, expected);
+        assert_eq!(lx.parse(" x"), None);
+
+        let dispatch = lx.dispatch();
+        let tokens: Vec<_> = dispatch.tokenize(code).collect();
+        assert_eq!(tokens, expected);
+
+        match lx.tokenize("x /* c").last() {
+            Some(ParseResult::Error(error)) => {
+                assert_eq!(error.to_string(), "unterminated comment starting at 2")
+            }
+            _ => unreachable!(),
+        }
+
+        let longest = lx.longest();
+        let tokens: Vec<_> = longest.tokenize(code).collect();
+        assert_eq!(tokens, expected);
+
+        let compiled = longest.compile();
+        let tokens: Vec<_> = compiled.tokenize(code).collect();
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn empty_skip() {
+        use crate::span::Span;
+
+        let name = pat('a'..='z') * (1..);
+        let lx = skip(pat(' ') * ..) | lex(name, Token::Name) | lex('=', Token::Eq);
+
+        let code = "a b=";
+        let expected = [
+            ParseResult::Ok(Token::Name, Span::of(code, 0..1)),
+            ParseResult::Ok(Token::Name, Span::of(code, 2..3)),
+            ParseResult::Ok(Token::Eq, Span::of(code, 3..4)),
+        ];
+
+        let tokens: Vec<_> = lx.tokenize(code).collect();
+        assert_eq!(tokens, expected);
+
+        let tokens: Vec<_> = lx.dispatch().tokenize(code).collect();
+        assert_eq!(tokens, expected);
+
+        match lx.tokenize("a ?").last() {
+            Some(ParseResult::Error(error)) => assert_eq!(error.position.offset, 2),
+            _ => unreachable!(),
+        }
+
+        let longest = lx.longest();
+        let tokens: Vec<_> = longest.tokenize(code).collect();
+        assert_eq!(tokens, expected);
+
+        let tokens: Vec<_> = longest.compile().tokenize(code).collect();
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn skip_without_token() {
+        use crate::read_token::with_context;
+
+        let space = lex(
+            ' ',
+            with_context(|_: &str, _, count: &mut usize| {
+                *count += 1;
+                Token::Eq
+            }),
+        )
+        .skip();
+        let lx = space | lex(pat('a'..='z') * (1..), Token::Name);
+
+        let mut count = 0;
+        let tokens: Vec<_> = lx.tokenize("a b").context(&mut count).collect();
+        assert_eq!(tokens.len(), 2);
+
+        let longest = lx.longest();
+        let compiled = longest.compile();
+        let tokens: Vec<_> = compiled.tokenize("a b").context(&mut count).collect();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(count, 0);
+    }
 }
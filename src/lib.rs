@@ -1,15 +1,41 @@
 #[cfg_attr(test, macro_use)]
 extern crate read_token_derive;
+extern crate self as lexp;
 
 pub use read_token_derive::ReadToken;
 
+mod captures;
+mod cursor;
+mod dfa;
+mod diagnostic;
+mod dispatch;
+mod error;
+mod first_chars;
+mod incremental;
+mod input;
+mod interner;
 mod lexer;
+mod modes;
 mod parse;
+mod pratt;
 mod read_pattern;
 mod read_token;
+mod recovery;
+mod regex;
+mod span;
+mod stream;
+mod token_tree;
+pub mod bytes {
+    mod byte_lexer;
+    mod byte_pattern;
+
+    pub use byte_lexer::{lex_bytes, ReadByteToken};
+    pub use byte_pattern::{bpat, ReadBytes};
+}
 pub mod patterns {
     mod and_pattern;
     mod any_pattern;
+    mod backtrack_pattern;
     mod capture;
     mod many_pattern;
     mod or_pattern;
@@ -19,7 +45,8 @@ pub mod patterns {
 
     pub use and_pattern::AndPattern;
     pub use any_pattern::{AnyPattern, ANY};
-    pub use capture::{cap, Capture};
+    pub use backtrack_pattern::BacktrackPattern;
+    pub use capture::{cap, cap_named, Capture, CapturePolicy, NamedCapture};
     pub use many_pattern::ManyPattern;
     pub use or_pattern::OrPattern;
     pub use pattern::{pat, Pattern};
@@ -27,9 +54,34 @@ pub mod patterns {
     pub use until_pattern::UntilPattern;
 }
 
-pub use lexer::{lex, Lexeme, Lexer};
-pub use parse::{Parse, ParseIterator, ParseResult};
+pub mod token_patterns {
+    mod token_pattern;
+
+    pub use token_pattern::{kind, tok, tpat, ReadTokens, TokenEq, TokenKind};
+}
+
+pub use captures::{CaptureIndex, Captures};
+pub use cursor::{Checkpoint, ExpectError, ExpectResult, TokenCursor};
+pub use dfa::{Compiled, Dfa};
+pub use diagnostic::Diagnostic;
+pub use dispatch::Dispatch;
+pub use error::{Furthest, LexError, TokenError};
+pub use first_chars::FirstChars;
+pub use incremental::Edit;
+pub use input::{Input, Unit};
+pub use interner::{intern, Interner, Symbol};
+pub use lexer::{lex, skip, Lexeme, Lexer, Longest, Skip};
+pub use modes::{Action, Modes};
+pub use parse::{Lexed, Parse, ParseIterator, ParseResult, ParseWith};
+pub use pratt::{Assoc, Pratt, PrattError, TokenSource};
 pub use read_pattern::ReadPattern;
-pub use read_token::ReadToken;
+pub use read_token::{
+    with_context, ReadToken, TokenResult, TryReadToken, TryReadTokenWith, WithContext,
+};
+pub use recovery::{Recovery, SkipChar, SkipTo, SkipToWhitespace};
+pub use regex::Regex;
+pub use span::{Column, Position, Span};
+pub use stream::StreamIterator;
+pub use token_tree::{DelimiterError, Group, TokenTree, TreeBuilder, TreeResult};
 
 mod tests;
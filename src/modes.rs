@@ -0,0 +1,289 @@
+use crate::error::Furthest;
+use crate::first_chars::FirstChars;
+use crate::input::Input;
+use crate::parse::{Lexed, Parse, ParseWith};
+
+/// Change of the lexer mode after a lexeme is matched
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Keep the current mode
+    None,
+    /// Enter the mode, keeping the current one on the stack
+    Push(usize),
+    /// Return to the previous mode
+    Pop,
+    /// Replace the current mode
+    Switch(usize),
+}
+
+impl Action {
+    pub(crate) fn apply(self, stack: &mut Vec<usize>) {
+        match self {
+            Action::None => {}
+            Action::Push(mode) => stack.push(mode),
+            Action::Pop => {
+                // The initial mode is never popped
+                if stack.len() > 1 {
+                    stack.pop();
+                }
+            }
+            Action::Switch(mode) => {
+                if let Some(top) = stack.last_mut() {
+                    *top = mode;
+                }
+            }
+        }
+    }
+}
+
+/// Set of lexers where only one is active at a time
+///
+/// Like start conditions in flex, modes allow the valid tokens to depend
+/// on the context, for example inside of a string with interpolation.
+/// The mode is an index of the lexer in the order of addition,
+/// so the first lexer is the mode `0`, which is the initial one.
+/// Lexemes change the mode with `push`, `pop` and `switch`,
+/// and `ParseIterator` keeps the stack of modes.
+/// Entering the mode which isn't added stops lexing with an error.
+///
+pub struct Modes<'p, 't, T, C = (), I = str>
+where
+    I: Input + ?Sized,
+{
+    modes: Vec<Box<dyn ParseWith<'t, C, I, Token = T> + 'p>>,
+}
+
+impl<'p, 't, T, C, I> Modes<'p, 't, T, C, I>
+where
+    I: Input + ?Sized,
+{
+    pub fn new<P>(initial: P) -> Self
+    where
+        P: ParseWith<'t, C, I, Token = T> + 'p,
+    {
+        Modes {
+            modes: vec![Box::new(initial)],
+        }
+    }
+
+    /// Adds a lexer of the next mode
+    pub fn mode<P>(mut self, parser: P) -> Self
+    where
+        P: ParseWith<'t, C, I, Token = T> + 'p,
+    {
+        self.modes.push(Box::new(parser));
+        self
+    }
+}
+
+impl<'p, 't, T, C, I> Parse<'t, I> for Modes<'p, 't, T, C, I>
+where
+    I: Input + ?Sized,
+{
+    type Token = T;
+
+    fn read_len(&self, text: &'t I, mode: usize) -> Option<usize> {
+        self.modes.get(mode)?.read_len(text, mode)
+    }
+
+    fn read_furthest(&self, text: &'t I, mode: usize) -> Furthest {
+        match self.modes.get(mode) {
+            Some(parser) => parser.read_furthest(text, mode),
+            None => Furthest::default(),
+        }
+    }
+
+    fn has_modes(&self) -> bool {
+        true
+    }
+
+    /// Chars of all modes, since the current mode isn't known
+    fn first_chars(&self) -> FirstChars {
+        self.modes.iter().fold(FirstChars::none(), |set, parser| {
+            set.union(parser.first_chars())
+        })
+    }
+}
+
+impl<'p, 't, T, C, I> ParseWith<'t, C, I> for Modes<'p, 't, T, C, I>
+where
+    I: Input + ?Sized,
+{
+    fn parse_action(
+        &self,
+        text: &'t I,
+        pos: usize,
+        mode: usize,
+        ctx: &mut C,
+    ) -> Option<Lexed<Self::Token>> {
+        self.modes.get(mode)?.parse_action(text, pos, mode, ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dfa::Compiled;
+    use crate::dispatch::Dispatch;
+    use crate::lexer::lex;
+    use crate::parse::ParseResult;
+    use crate::patterns::pat;
+    use crate::span::{Position, Span};
+
+    #[derive(Copy, Clone, PartialEq, Debug, ReadToken)]
+    enum Token {
+        Name,
+        Plus,
+        Quote,
+        Text,
+        InterpStart,
+        InterpEnd,
+        Heredoc,
+    }
+
+    const STRING: usize = 1;
+
+    #[test]
+    fn modes() {
+        let main = lex(pat('a'..='z') * (1..), Token::Name)
+            | lex('+', Token::Plus)
+            | lex('"', Token::Quote).push(STRING)
+            | lex('}', Token::InterpEnd).pop();
+
+        let text = pat(|c: char| c != '"' && c != '$') * (1..);
+        let string = lex('"', Token::Quote).pop()
+            | lex("${", Token::InterpStart).push(0)
+            | lex(text, Token::Text);
+
+        let modes = Modes::new(main).mode(string);
+        let tokens: Vec<_> = modes
+            .tokenize(r#"a+"x ${b+"y"} z"+c"#)
+            .map(|r| match r {
+                ParseResult::Ok(tok, _) => tok,
+                _ => unreachable!(),
+            })
+            .collect();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Name,
+                Token::Plus,
+                Token::Quote,
+                Token::Text,
+                Token::InterpStart,
+                Token::Name,
+                Token::Plus,
+                Token::Quote,
+                Token::Text,
+                Token::Quote,
+                Token::InterpEnd,
+                Token::Text,
+                Token::Quote,
+                Token::Plus,
+                Token::Name,
+            ]
+        );
+
+        // Spaces are valid only inside of strings
+        let code = "a b";
+        let mut iter = modes.tokenize(code);
+        assert_eq!(
+            iter.next(),
+            Some(ParseResult::Ok(Token::Name, Span::of(code, 0..1)))
+        );
+        match iter.next() {
+            Some(ParseResult::Error(error)) => assert_eq!(error.position, Position::at(code, 1)),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn switch_mode() {
+        const HEREDOC: usize = 1;
+
+        let main = lex("<<", Token::Heredoc).switch(HEREDOC) | lex('+', Token::Plus);
+        let heredoc = lex("END", Token::Heredoc).switch(0)
+            | lex(pat(|c: char| c != 'E') * (1..), Token::Text);
+
+        let modes = Modes::new(main).mode(heredoc);
+        let code = "+<<a+bEND+";
+        let mut iter = modes.tokenize(code);
+        assert_eq!(iter.mode(), 0);
+        assert_eq!(
+            iter.next(),
+            Some(ParseResult::Ok(Token::Plus, Span::of(code, 0..1)))
+        );
+        assert_eq!(
+            iter.next(),
+            Some(ParseResult::Ok(Token::Heredoc, Span::of(code, 1..3)))
+        );
+        assert_eq!(iter.mode(), HEREDOC);
+        assert_eq!(
+            iter.next(),
+            Some(ParseResult::Ok(Token::Text, Span::of(code, 3..6)))
+        );
+        assert_eq!(
+            iter.next(),
+            Some(ParseResult::Ok(Token::Heredoc, Span::of(code, 6..9)))
+        );
+        assert_eq!(iter.mode(), 0);
+        assert_eq!(
+            iter.next(),
+            Some(ParseResult::Ok(Token::Plus, Span::of(code, 9..10)))
+        );
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn dispatch_modes() {
+        let main = lex(pat('a'..='z') * (1..), Token::Name) | lex('"', Token::Quote).push(STRING);
+        let string = lex('"', Token::Quote).pop() | lex(pat(' ') * (1..), Token::Text);
+        let modes = Modes::new(main).mode(string);
+
+        let code = "a\"  \"b";
+        let expected = [
+            ParseResult::Ok(Token::Name, Span::of(code, 0..1)),
+            ParseResult::Ok(Token::Quote, Span::of(code, 1..2)),
+            ParseResult::Ok(Token::Text, Span::of(code, 2..4)),
+            ParseResult::Ok(Token::Quote, Span::of(code, 4..5)),
+            ParseResult::Ok(Token::Name, Span::of(code, 5..6)),
+        ];
+
+        let dispatch = Dispatch::new(&modes);
+        let tokens: Vec<_> = dispatch.tokenize(code).collect();
+        assert_eq!(tokens, expected);
+
+        let compiled = Compiled::new(&modes);
+        let tokens: Vec<_> = compiled.tokenize(code).collect();
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn action() {
+        let mut stack = vec![0];
+        Action::Push(1).apply(&mut stack);
+        Action::Switch(2).apply(&mut stack);
+        assert_eq!(stack, [0, 2]);
+        Action::Pop.apply(&mut stack);
+        Action::Pop.apply(&mut stack);
+        assert_eq!(stack, [0]);
+    }
+
+    #[test]
+    fn unknown_mode() {
+        let modes = Modes::new(lex('a', Token::Name).push(2) | lex('+', Token::Plus));
+        let code = "a+";
+        let mut iter = modes.tokenize(code);
+        assert_eq!(
+            iter.next(),
+            Some(ParseResult::Ok(Token::Name, Span::of(code, 0..1)))
+        );
+        assert_eq!(iter.mode(), 2);
+        match iter.next() {
+            Some(ParseResult::Error(error)) => assert_eq!(error.position, Position::at(code, 1)),
+            _ => unreachable!(),
+        }
+        assert_eq!(iter.next(), None);
+    }
+}
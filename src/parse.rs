@@ -1,14 +1,113 @@
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub enum ParseResult<T> {
-    Ok(T, usize),
-    UnexpectedAt(usize),
+use crate::error::{Furthest, LexError, TokenError};
+use crate::first_chars::FirstChars;
+use crate::input::Input;
+use crate::modes::Action;
+use crate::recovery::Recovery;
+use crate::regex::Regex;
+use crate::span::{Position, Span};
+
+/// Result of lexing, where the unit `U` of errors is `char` for text and `u8` for bytes
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseResult<T, U = char> {
+    Ok(T, Span),
+    Error(LexError<U>),
+    /// Text which a lexeme matched, but failed to convert into a token
+    Invalid(TokenError, Span),
+    /// Unexpected text, which was skipped
+    Skipped(Span),
+}
+
+impl<T, U> ParseResult<T, U> {
+    /// Span of the result, which is empty for `Error`
+    pub fn span(&self) -> Span {
+        match self {
+            ParseResult::Ok(_, span)
+            | ParseResult::Invalid(_, span)
+            | ParseResult::Skipped(span) => *span,
+            ParseResult::Error(error) => Span::new(error.position, error.position),
+        }
+    }
 }
 
-pub trait Parse<'t> {
+/// Token of the matched lexeme with its length and action
+///
+/// The token is `Err` if the conversion fails, and `None` if the lexeme is skipped.
+pub type Lexed<T> = (Result<Option<T>, TokenError>, usize, Action);
+
+/// Parser of the input `I`, which is text by default and `[u8]` for bytes
+pub trait Parse<'t, I: Input + ?Sized = str> {
     type Token;
-    fn parse(&self, text: &'t str) -> Option<(Self::Token, usize)>;
 
-    fn tokenize<'p>(&'p self, text: &'t str) -> ParseIterator<'p, 't, Self>
+    fn parse(&self, text: &'t I) -> Option<(Self::Token, usize)>
+    where
+        Self: Sized + ParseWith<'t, (), I>,
+    {
+        self.parse_at(text, 0)
+    }
+
+    /// Parses the `text` which starts at `pos` of the whole text
+    ///
+    /// The position is used to make absolute spans of captures.
+    /// The first lexeme which matches is taken like in `tokenize`, so if it fails
+    /// to convert the matched text or is skipped, the text isn't parsed.
+    /// Lexers which need a context don't parse without it.
+    fn parse_at(&self, text: &'t I, pos: usize) -> Option<(Self::Token, usize)>
+    where
+        Self: Sized + ParseWith<'t, (), I>,
+    {
+        match self.parse_action(text, pos, 0, &mut ())? {
+            (Ok(Some(tok)), len, _) => Some((tok, len)),
+            _ => None,
+        }
+    }
+
+    /// Finds how far lexemes reached in the `mode` in the text, which none of them matches
+    fn read_furthest(&self, _text: &'t I, _mode: usize) -> Furthest {
+        Furthest::default()
+    }
+
+    /// Name of the lexeme to report errors
+    fn name(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Action of the lexeme which changes the lexer mode
+    fn action(&self) -> Action {
+        Action::None
+    }
+
+    /// Priority of the parser in the longest match mode
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// Checks if tokens depend on the lexer mode, like tokens of `Modes`
+    fn has_modes(&self) -> bool {
+        false
+    }
+
+    /// Reads the length of the match in the `mode` without making a token
+    fn read_len(&self, text: &'t I, mode: usize) -> Option<usize>;
+
+    /// Returns the set of characters which can start a token
+    fn first_chars(&self) -> FirstChars {
+        FirstChars::any()
+    }
+
+    /// Converts the pattern of the lexeme into a regex to compile it
+    fn to_regex(&self) -> Option<Regex> {
+        None
+    }
+
+    /// Visits every lexeme of the parser in declaration order
+    fn for_each_lexeme<'p>(&'p self, f: &mut dyn FnMut(&'p dyn Parse<'t, I, Token = Self::Token>))
+    where
+        Self: Sized,
+    {
+        f(self)
+    }
+
+    fn tokenize<'p>(&'p self, text: &'t I) -> ParseIterator<'p, 't, Self, (), I>
     where
         Self: Sized,
     {
@@ -16,59 +115,263 @@ pub trait Parse<'t> {
     }
 }
 
-pub struct ParseIterator<'p, 't, P> {
-    parser: &'p P,
-    rest: &'t str,
-    parsed_len: usize,
+/// Parser which makes tokens with the context `C` of `ParseIterator`
+///
+/// Lexemes with constructors made by `with_context` parse only with
+/// the context of their type, and other parsers with any context.
+/// So a lexer which needs a context can't be used without it.
+///
+pub trait ParseWith<'t, C, I: Input + ?Sized = str>: Parse<'t, I> {
+    /// Parses the token which is exactly `len` bytes long in the `mode`
+    ///
+    /// This is how a token is made after its length is found by an automaton,
+    /// so the `len` must be one the parser can match. Only lexemes with
+    /// captures match the text again to read them.
+    /// Skipped lexemes return `Ok(None)`.
+    fn parse_exact(
+        &self,
+        text: &'t I,
+        len: usize,
+        pos: usize,
+        mode: usize,
+        ctx: &mut C,
+    ) -> Option<Result<Option<Self::Token>, TokenError>> {
+        match self.parse_action(text, pos, mode, ctx)? {
+            (tok, l, _) if l == len => Some(tok),
+            _ => None,
+        }
+    }
+
+    /// Parses the token in the `mode` and returns the action of the matched lexeme
+    ///
+    /// Only `Modes` depends on the mode, other parsers pass it through.
+    /// Unlike `parse_at`, it returns the error of the token conversion,
+    /// `Ok(None)` for skipped lexemes, and passes the context to token constructors.
+    fn parse_action(
+        &self,
+        text: &'t I,
+        pos: usize,
+        mode: usize,
+        ctx: &mut C,
+    ) -> Option<Lexed<Self::Token>>;
+
+    /// Visits every lexeme of the parser like `for_each_lexeme`, with the context
+    fn for_each_lexeme_with<'p>(
+        &'p self,
+        f: &mut dyn FnMut(&'p dyn ParseWith<'t, C, I, Token = Self::Token>),
+    ) where
+        Self: Sized,
+    {
+        f(self)
+    }
+}
+
+pub struct ParseIterator<'p, 't, P, C = (), I = str>
+where
+    P: Parse<'t, I>,
+    I: Input + ?Sized,
+{
+    pub(crate) parser: &'p P,
+    pub(crate) text: &'t I,
+    rest: &'t I,
+    position: Position,
+    modes: Vec<usize>,
+    recovery: Option<Box<dyn Recovery<I> + 'p>>,
+    context: Context<'p, C>,
+    pending: Option<ParseResult<P::Token, I::Unit>>,
     end: bool,
 }
 
-impl<'p, 't, P> ParseIterator<'p, 't, P>
+/// Context of an iterator, which owns the unit context of iterators made without one
+pub(crate) enum Context<'p, C> {
+    Owned(C),
+    Borrowed(&'p mut C),
+}
+
+impl<'p, C> Context<'p, C> {
+    pub(crate) fn get(&mut self) -> &mut C {
+        match self {
+            Context::Owned(ctx) => ctx,
+            Context::Borrowed(ctx) => ctx,
+        }
+    }
+}
+
+impl<'p, 't, P, I> ParseIterator<'p, 't, P, (), I>
 where
-    P: Parse<'t>,
+    P: Parse<'t, I>,
+    I: Input + ?Sized,
 {
-    pub fn new(parser: &'p P, text: &'t str) -> Self {
+    pub fn new(parser: &'p P, text: &'t I) -> Self {
         ParseIterator {
             parser,
+            text,
             rest: text,
-            parsed_len: 0,
+            position: Position::default(),
+            modes: vec![0],
+            recovery: None,
+            context: Context::Owned(()),
+            pending: None,
             end: false,
         }
     }
 }
 
-impl<'p, 't, P> Iterator for ParseIterator<'p, 't, P>
+impl<'p, 't, P, C, I> ParseIterator<'p, 't, P, C, I>
+where
+    P: Parse<'t, I>,
+    I: Input + ?Sized,
+{
+    /// Enables the error recovery
+    ///
+    /// Instead of stopping on unexpected text, the iterator skips it
+    /// by the `recovery` strategy until some lexeme matches again.
+    pub fn recover<R>(mut self, recovery: R) -> Self
+    where
+        R: Recovery<I> + 'p,
+    {
+        self.recovery = Some(Box::new(recovery));
+        self
+    }
+
+    /// Passes the context to token constructors
+    ///
+    /// Constructors made by `with_context` get the context, which allows
+    /// them to intern strings or to depend on previous tokens.
+    pub fn context<D>(self, ctx: &'p mut D) -> ParseIterator<'p, 't, P, D, I> {
+        ParseIterator {
+            parser: self.parser,
+            text: self.text,
+            rest: self.rest,
+            position: self.position,
+            modes: self.modes,
+            recovery: self.recovery,
+            context: Context::Borrowed(ctx),
+            pending: self.pending,
+            end: self.end,
+        }
+    }
+
+    /// Starts lexing at the `position` of the text
+    ///
+    /// The position must be at a token boundary, like the end of a token
+    /// of the previous lexing. Lexing starts in the initial mode.
+    pub fn start_at(mut self, position: Position) -> Self {
+        self.rest = &self.text[position.offset..];
+        self.position = position;
+        self
+    }
+
+    /// Current lexer mode
+    pub fn mode(&self) -> usize {
+        self.modes[self.modes.len() - 1]
+    }
+
+    /// Position of the rest of the text
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    fn advance(&mut self, len: usize) {
+        self.rest = &self.rest[len..];
+        self.position = self.position.advance(self.text, len);
+    }
+}
+
+impl<'p, 't, P, C, I> ParseIterator<'p, 't, P, C, I>
+where
+    P: ParseWith<'t, C, I>,
+    I: Input + ?Sized,
+{
+    fn read_token(&mut self) -> Option<ParseResult<P::Token, I::Unit>> {
+        loop {
+            let mode = self.mode();
+            let start = self.position;
+            let (tok, len, action) =
+                self.parser
+                    .parse_action(self.rest, start.offset, mode, self.context.get())?;
+            action.apply(&mut self.modes);
+            self.advance(len);
+
+            let span = Span::new(start, self.position);
+            match tok {
+                Ok(Some(tok)) => return Some(ParseResult::Ok(tok, span)),
+                Err(error) => return Some(ParseResult::Invalid(error, span)),
+                // Skipped lexemes make no item, the empty one can't move further
+                Ok(None) if len == 0 || self.rest.is_empty() => return None,
+                Ok(None) => {}
+            }
+        }
+    }
+}
+
+impl<'p, 't, P, C, I> Iterator for ParseIterator<'p, 't, P, C, I>
 where
-    P: Parse<'t>,
+    P: ParseWith<'t, C, I>,
+    I: Input + ?Sized,
 {
-    type Item = ParseResult<P::Token>;
+    type Item = ParseResult<P::Token, I::Unit>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(item) = self.pending.take() {
+            return Some(item);
+        }
+
         if self.end {
             return None;
         }
 
-        match self.parser.parse(self.rest) {
-            Some((tok, len)) => {
-                self.rest = &self.rest[len..];
-                let pos = self.parsed_len;
-                self.parsed_len += len;
-                Some(ParseResult::Ok(tok, pos))
-            }
-            None if self.rest.is_empty() => None,
+        if let Some(item) = self.read_token() {
+            return Some(item);
+        }
+
+        if self.rest.is_empty() {
+            return None;
+        }
+
+        let start = self.position;
+        let recovery = match self.recovery.take() {
+            Some(recovery) => recovery,
             None => {
                 self.end = true;
-                Some(ParseResult::UnexpectedAt(self.parsed_len))
+                let furthest = self.parser.read_furthest(self.rest, self.mode());
+                let error = LexError::new(self.text, start, furthest);
+                return Some(ParseResult::Error(error));
             }
-        }
+        };
+
+        // The skipped text is reported once, before the next token
+        let end = loop {
+            let mut len = recovery.skip(self.rest).clamp(1, self.rest.len());
+            while !self.rest.is_boundary(len) {
+                len += 1;
+            }
+
+            self.advance(len);
+            let end = self.position;
+            if self.rest.is_empty() {
+                break end;
+            }
+
+            if let Some(item) = self.read_token() {
+                self.pending = Some(item);
+                break end;
+            }
+        };
+
+        self.recovery = Some(recovery);
+        Some(ParseResult::Skipped(Span::new(start, end)))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::captures::Captures;
     use crate::lexer::lex;
-    use crate::read_token::ReadToken;
+    use crate::patterns::{cap, pat};
+    use crate::read_token::with_context;
+    use crate::recovery::{SkipChar, SkipTo, SkipToWhitespace};
 
     #[derive(Copy, Clone, PartialEq, Debug, ReadToken)]
     enum Token {
@@ -80,6 +383,8 @@ mod tests {
         LeftBracket,
         RightBracket,
         Semicolon,
+        Num(usize, usize),
+        Byte(u8),
     }
 
     #[test]
@@ -98,7 +403,7 @@ mod tests {
             .tokenize(code)
             .map(|r| match r {
                 ParseResult::Ok(tok, _) => tok,
-                ParseResult::UnexpectedAt(_) => unreachable!(),
+                _ => unreachable!(),
             })
             .collect();
 
@@ -118,4 +423,225 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn tokenize_caps() {
+        let num = (pat('-') * ..=1) & cap(pat('0'..='9') * (1..));
+        let lx = lex('+', Token::Plus)
+            | lex(num, |_, caps: Captures| {
+                let span = caps.absolute_span(0).unwrap();
+                Token::Num(span.start, span.end)
+            });
+
+        let code = "1+-23";
+        let tokens: Vec<_> = lx.tokenize(code).collect();
+        assert_eq!(
+            tokens,
+            [
+                ParseResult::Ok(Token::Num(0, 1), Span::of(code, 0..1)),
+                ParseResult::Ok(Token::Plus, Span::of(code, 1..2)),
+                ParseResult::Ok(Token::Num(3, 5), Span::of(code, 2..5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_invalid() {
+        let byte = pat('0'..='9') * (1..);
+        let lx = lex('+', Token::Plus) | lex(byte, |n: &str, _| n.parse().map(Token::Byte));
+
+        let code = "1+300+2";
+        let tokens: Vec<_> = lx.tokenize(code).collect();
+        assert_eq!(
+            tokens,
+            [
+                ParseResult::Ok(Token::Byte(1), Span::of(code, 0..1)),
+                ParseResult::Ok(Token::Plus, Span::of(code, 1..2)),
+                ParseResult::Invalid(
+                    TokenError::new("300".parse::<u8>().unwrap_err()),
+                    Span::of(code, 2..5)
+                ),
+                ParseResult::Ok(Token::Plus, Span::of(code, 5..6)),
+                ParseResult::Ok(Token::Byte(2), Span::of(code, 6..7)),
+            ]
+        );
+
+        assert_eq!(tokens[2].span().range(), 2..5);
+
+        // The invalid match isn't passed to the next lexeme, like in `tokenize`
+        let lx = lx | lex("300", Token::Plus);
+        assert_eq!(lx.parse("300"), None);
+        assert_eq!(lx.parse("+"), Some((Token::Plus, 1)));
+        assert_eq!(lx.parse("12"), Some((Token::Byte(12), 2)));
+    }
+
+    struct Context {
+        keywords: Vec<&'static str>,
+        names: Vec<String>,
+        depth: usize,
+    }
+
+    #[test]
+    fn tokenize_context() {
+        let name = pat('a'..='z') * (1..);
+        let lx = lex(';', Token::Semicolon)
+            | lex(
+                '(',
+                with_context(|_, _, ctx: &mut Context| {
+                    ctx.depth += 1;
+                    Token::LeftBracket
+                }),
+            )
+            | lex(
+                ')',
+                with_context(|_, _, ctx: &mut Context| {
+                    ctx.depth -= 1;
+                    Token::RightBracket
+                }),
+            )
+            | lex(
+                name,
+                with_context(|text: &str, _, ctx: &mut Context| {
+                    if ctx.keywords.contains(&text) {
+                        return Token::X;
+                    }
+
+                    let idx = match ctx.names.iter().position(|name| name == text) {
+                        Some(idx) => idx,
+                        None => {
+                            ctx.names.push(text.to_string());
+                            ctx.names.len() - 1
+                        }
+                    };
+
+                    Token::Num(idx, ctx.depth)
+                }),
+            );
+
+        let mut ctx = Context {
+            keywords: vec!["x"],
+            names: Vec::new(),
+            depth: 0,
+        };
+
+        let tokens: Vec<_> = lx
+            .tokenize("a(b(a)x)b;")
+            .context(&mut ctx)
+            .map(|r| match r {
+                ParseResult::Ok(tok, _) => tok,
+                _ => unreachable!(),
+            })
+            .collect();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Num(0, 0),
+                Token::LeftBracket,
+                Token::Num(1, 1),
+                Token::LeftBracket,
+                Token::Num(0, 2),
+                Token::RightBracket,
+                Token::X,
+                Token::RightBracket,
+                Token::Num(1, 0),
+                Token::Semicolon,
+            ]
+        );
+        assert_eq!(ctx.names, ["a", "b"]);
+        assert_eq!(ctx.depth, 0);
+
+        // Dispatch tables and compiled lexers take the context of lexemes
+        let dispatch = lx.dispatch();
+        let tokens: Vec<_> = dispatch.tokenize("c(a)").context(&mut ctx).collect();
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(ctx.names, ["a", "b", "c"]);
+
+        let longest = lx.longest();
+        let compiled = longest.compile();
+        let tokens: Vec<_> = compiled.tokenize("d;").context(&mut ctx).collect();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(ctx.names.len(), 4);
+    }
+
+    #[test]
+    fn recover() {
+        let lx = lex('+', Token::Plus) | lex('1', Token::One) | lex(' ', Token::Semicolon);
+
+        let code = "1+ab+1";
+        let tokens: Vec<_> = lx.tokenize(code).collect();
+        assert_eq!(
+            tokens,
+            [
+                ParseResult::Ok(Token::One, Span::of(code, 0..1)),
+                ParseResult::Ok(Token::Plus, Span::of(code, 1..2)),
+                ParseResult::Error(LexError::new(
+                    code,
+                    Position::at(code, 2),
+                    Furthest::default()
+                )),
+            ]
+        );
+
+        let tokens: Vec<_> = lx.tokenize(code).recover(SkipChar).collect();
+        assert_eq!(
+            tokens,
+            [
+                ParseResult::Ok(Token::One, Span::of(code, 0..1)),
+                ParseResult::Ok(Token::Plus, Span::of(code, 1..2)),
+                ParseResult::Skipped(Span::of(code, 2..4)),
+                ParseResult::Ok(Token::Plus, Span::of(code, 4..5)),
+                ParseResult::Ok(Token::One, Span::of(code, 5..6)),
+            ]
+        );
+
+        let code = "ab+ c";
+        let tokens: Vec<_> = lx.tokenize(code).recover(SkipToWhitespace).collect();
+        assert_eq!(
+            tokens,
+            [
+                ParseResult::Skipped(Span::of(code, 0..3)),
+                ParseResult::Ok(Token::Semicolon, Span::of(code, 3..4)),
+                ParseResult::Skipped(Span::of(code, 4..5)),
+            ]
+        );
+
+        let code = "1ф+1 1";
+        let tokens: Vec<_> = lx.tokenize(code).recover(SkipTo(' ')).collect();
+        assert_eq!(
+            tokens,
+            [
+                ParseResult::Ok(Token::One, Span::of(code, 0..1)),
+                ParseResult::Skipped(Span::of(code, 1..5)),
+                ParseResult::Ok(Token::Semicolon, Span::of(code, 5..6)),
+                ParseResult::Ok(Token::One, Span::of(code, 6..7)),
+            ]
+        );
+
+        // The length inside of a char is rounded up to its end
+        let tokens: Vec<_> = lx.tokenize(code).recover(|_: &str| 1).collect();
+        assert_eq!(tokens[1], ParseResult::Skipped(Span::of(code, 1..3)));
+        assert_eq!(
+            tokens[2],
+            ParseResult::Ok(Token::Plus, Span::of(code, 3..4))
+        );
+    }
+
+    #[test]
+    fn tokenize_spans() {
+        let lx = lex('x', Token::X) | lex('\n', Token::Semicolon) | lex("ф", Token::One);
+
+        let code = "x\nфx";
+        let spans: Vec<_> = lx.tokenize(code).map(|r| r.span()).collect();
+        assert_eq!(spans.len(), 4);
+        assert_eq!((spans[0].start.line, spans[0].end.column.chars), (0, 1));
+        assert_eq!((spans[1].end.line, spans[1].end.column.chars), (1, 0));
+        assert_eq!(spans[2].range(), 2..4);
+        assert_eq!(spans[2].end.column.chars, 1);
+        assert_eq!(spans[3].start, Position::at(code, 4));
+
+        let mut iter = lx.tokenize(code);
+        iter.by_ref().for_each(drop);
+        assert_eq!(iter.position(), Position::at(code, code.len()));
+    }
 }
@@ -1,4 +1,7 @@
-use crate::read_pattern::ReadPattern;
+use crate::captures::Captures;
+use crate::first_chars::FirstChars;
+use crate::read_pattern::{push_len, ReadPattern};
+use crate::regex::Regex;
 
 #[derive(Copy, Clone, Debug)]
 pub struct AndPattern<L, R>(pub L, pub R);
@@ -14,10 +17,68 @@ where
         Some(len_a + len_b)
     }
 
-    fn read_captures<'t>(&self, text: &'t str, buf: &mut Vec<&'t str>) -> Option<usize> {
-        let len_a = self.0.read_captures(text, buf)?;
-        let len_b = self.1.read_captures(&text[len_a..], buf)?;
-        Some(len_a + len_b)
+    fn read_pattern_all(&self, text: &str) -> Vec<usize> {
+        let mut lens = Vec::new();
+        for len_a in self.0.read_pattern_all(text) {
+            for len_b in self.1.read_pattern_all(&text[len_a..]) {
+                push_len(&mut lens, len_a + len_b);
+            }
+        }
+
+        lens
+    }
+
+    fn read_captures<'t>(&self, text: &'t str, caps: &mut Captures<'t>) -> Option<usize> {
+        let start = caps.len();
+        let len_a = self.0.read_captures(text, caps)?;
+        match self.1.read_captures(&text[len_a..], caps) {
+            Some(len_b) => Some(len_a + len_b),
+            None => {
+                caps.truncate(start);
+                None
+            }
+        }
+    }
+
+    fn read_captures_exact<'t>(&self, text: &'t str, len: usize, caps: &mut Captures<'t>) -> bool {
+        for len_a in self.0.read_pattern_all(text) {
+            if len_a > len {
+                continue;
+            }
+
+            let rest = &text[len_a..];
+            if self.1.read_pattern_all(rest).contains(&(len - len_a)) {
+                self.0.read_captures_exact(text, len_a, caps);
+                self.1.read_captures_exact(rest, len - len_a, caps);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn push_unset_captures(&self, caps: &mut Captures) {
+        self.0.push_unset_captures(caps);
+        self.1.push_unset_captures(caps);
+    }
+
+    fn has_captures(&self) -> bool {
+        self.0.has_captures() || self.1.has_captures()
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        self.0.first_chars().then(self.1.first_chars())
+    }
+
+    fn to_regex(&self) -> Option<Regex> {
+        Some(Regex::Seq(vec![self.0.to_regex()?, self.1.to_regex()?]))
+    }
+
+    fn read_furthest(&self, text: &str) -> usize {
+        match self.0.read_pattern(text) {
+            Some(len_a) => len_a + self.1.read_furthest(&text[len_a..]),
+            None => self.0.read_furthest(text),
+        }
     }
 }
 
@@ -50,9 +111,20 @@ mod tests {
     #[test]
     fn and_pattern_caps() {
         let pattern = pat(cap(Pattern("foo"))) & cap("bar");
-        let mut caps = Vec::new();
+        let caps = pattern.captures("foobar").unwrap();
+        assert_eq!(caps.as_str(), "foobar");
+        assert_eq!(caps.to_vec(), ["foo", "bar"]);
+    }
+
+    #[test]
+    fn and_pattern_all() {
+        let pattern = pat("a") & (pat("b") | "bb");
+        assert_eq!(pattern.read_pattern_all("abb"), [2, 3]);
+        assert_eq!(pattern.read_pattern_all("ab"), [2]);
+        assert_eq!(pattern.read_pattern_all("a"), []);
 
-        assert_eq!(pattern.read_captures("foobar", &mut caps), Some(6));
-        assert_eq!(caps, ["foo", "bar"]);
+        let pattern = (pat("a") | "ab") & "c";
+        assert_eq!(pattern.read_pattern("abc"), None);
+        assert_eq!(pattern.read_pattern_all("abc"), [3]);
     }
 }
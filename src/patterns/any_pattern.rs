@@ -1,4 +1,6 @@
+use crate::first_chars::FirstChars;
 use crate::read_pattern::ReadPattern;
+use crate::regex::Regex;
 
 #[derive(Copy, Clone, Debug)]
 pub struct AnyPattern;
@@ -6,6 +8,7 @@ pub struct AnyPattern;
 pub const ANY: AnyPattern = AnyPattern;
 
 impl ReadPattern for AnyPattern {
+    #[allow(clippy::manual_map)]
     fn read_pattern(&self, text: &str) -> Option<usize> {
         if let Some(ch) = text.chars().next() {
             Some(ch.len_utf8())
@@ -13,6 +16,14 @@ impl ReadPattern for AnyPattern {
             None
         }
     }
+
+    fn first_chars(&self) -> FirstChars {
+        FirstChars::any_char()
+    }
+
+    fn to_regex(&self) -> Option<Regex> {
+        Some(Regex::Chars(vec!['\0'..=char::MAX]))
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,107 @@
+use crate::captures::Captures;
+use crate::first_chars::FirstChars;
+use crate::read_pattern::ReadPattern;
+use crate::regex::Regex;
+
+/// Pattern which matches like a regular expression
+///
+/// It tries every length of the inner pattern in order of preference
+/// and takes the first one instead of committing to a greedy choice.
+/// For example, `pat('/') & (pat(ANY) * ..) & '/'` never matches,
+/// but it matches `/text/` when wrapped into `BacktrackPattern`.
+///
+#[derive(Copy, Clone, Debug)]
+pub struct BacktrackPattern<T>(pub T);
+
+impl<T> ReadPattern for BacktrackPattern<T>
+where
+    T: ReadPattern,
+{
+    fn read_pattern(&self, text: &str) -> Option<usize> {
+        self.0.read_pattern_all(text).first().copied()
+    }
+
+    fn read_pattern_all(&self, text: &str) -> Vec<usize> {
+        self.0.read_pattern_all(text)
+    }
+
+    fn read_captures<'t>(&self, text: &'t str, caps: &mut Captures<'t>) -> Option<usize> {
+        let len = self.read_pattern(text)?;
+        self.0.read_captures_exact(text, len, caps);
+        Some(len)
+    }
+
+    fn read_captures_exact<'t>(&self, text: &'t str, len: usize, caps: &mut Captures<'t>) -> bool {
+        self.0.read_captures_exact(text, len, caps)
+    }
+
+    fn push_unset_captures(&self, caps: &mut Captures) {
+        self.0.push_unset_captures(caps);
+    }
+
+    fn test_pattern(&self, text: &str) -> bool {
+        self.0.read_pattern_all(text).contains(&text.len())
+    }
+
+    fn has_captures(&self) -> bool {
+        self.0.has_captures()
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        self.0.first_chars()
+    }
+
+    fn to_regex(&self) -> Option<Regex> {
+        self.0.to_regex()
+    }
+
+    fn read_furthest(&self, text: &str) -> usize {
+        self.0.read_furthest(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{cap, pat};
+    use crate::patterns::ANY;
+    use crate::ReadPattern;
+
+    #[test]
+    fn backtrack_pattern() {
+        let slashed_text = (pat('/') & (pat(ANY) * ..) & '/').backtrack();
+        assert!(slashed_text.test_pattern("/test/"));
+        assert!(slashed_text.test_pattern("//"));
+        assert!(!slashed_text.test_pattern("/test"));
+        assert_eq!(slashed_text.read_pattern("/a/b/c"), Some(5));
+
+        let alnum = pat('a'..='z') | ('0'..='9');
+        let ends_with_digit = ((alnum * (1..)) & ('0'..='9')).backtrack();
+        assert!(ends_with_digit.test_pattern("abc1"));
+        assert!(ends_with_digit.test_pattern("a12"));
+        assert!(!ends_with_digit.test_pattern("abc"));
+
+        // The first alternative no longer hides the second one
+        let b = (pat("") | "b").backtrack();
+        assert!(b.test_pattern(""));
+        assert!(b.test_pattern("b"));
+        assert_eq!(b.read_pattern("b"), Some(0));
+    }
+
+    #[test]
+    fn backtrack_pattern_caps() {
+        let slashed_text = (pat('/') & cap(pat(ANY) * ..) & '/').backtrack();
+        let caps = slashed_text.captures("/a/b").unwrap();
+        assert_eq!(caps.as_str(), "/a/");
+        assert_eq!(caps.to_vec(), ["a"]);
+
+        let digits = ((pat(cap('0'..='9')) * (1..)) & '0').backtrack();
+        let caps = digits.captures("120").unwrap();
+        assert_eq!(caps.as_str(), "120");
+        assert_eq!(caps.to_vec(), ["1", "2"]);
+
+        let last = ((pat(cap('0'..='9')) * (1..)).last_captures() & '0').backtrack();
+        let caps = last.captures("120").unwrap();
+        assert_eq!(caps.as_str(), "120");
+        assert_eq!(caps.to_vec(), ["2"]);
+    }
+}
@@ -1,8 +1,58 @@
+use crate::captures::Captures;
+use crate::first_chars::FirstChars;
+use crate::regex::Regex;
 use crate::ReadPattern;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Capture<T>(pub T);
 
+#[derive(Copy, Clone, Debug)]
+pub struct NamedCapture<T>(pub &'static str, pub T);
+
+fn read_group<'t, T>(
+    pattern: &T,
+    name: Option<&'static str>,
+    text: &'t str,
+    caps: &mut Captures<'t>,
+) -> Option<usize>
+where
+    T: ReadPattern,
+{
+    // The outer capture goes before the nested ones
+    let start = caps.len();
+    caps.push_group(name, &text[..0]);
+    match pattern.read_captures(text, caps) {
+        Some(len) => {
+            caps.set(start, &text[..len]);
+            Some(len)
+        }
+        None => {
+            caps.truncate(start);
+            None
+        }
+    }
+}
+
+fn read_group_exact<'t, T>(
+    pattern: &T,
+    name: Option<&'static str>,
+    text: &'t str,
+    len: usize,
+    caps: &mut Captures<'t>,
+) -> bool
+where
+    T: ReadPattern,
+{
+    let start = caps.len();
+    caps.push_group(name, &text[..len]);
+    if pattern.read_captures_exact(text, len, caps) {
+        true
+    } else {
+        caps.truncate(start);
+        false
+    }
+}
+
 impl<T> ReadPattern for Capture<T>
 where
     T: ReadPattern,
@@ -11,10 +61,87 @@ where
         self.0.read_pattern(text)
     }
 
-    fn read_captures<'t>(&self, text: &'t str, buf: &mut Vec<&'t str>) -> Option<usize> {
-        let len = self.0.read_pattern(text)?;
-        buf.push(&text[..len]);
-        Some(len)
+    fn read_pattern_all(&self, text: &str) -> Vec<usize> {
+        self.0.read_pattern_all(text)
+    }
+
+    fn read_captures<'t>(&self, text: &'t str, caps: &mut Captures<'t>) -> Option<usize> {
+        read_group(&self.0, None, text, caps)
+    }
+
+    fn read_captures_exact<'t>(&self, text: &'t str, len: usize, caps: &mut Captures<'t>) -> bool {
+        read_group_exact(&self.0, None, text, len, caps)
+    }
+
+    fn push_unset_captures(&self, caps: &mut Captures) {
+        caps.push_unset(None);
+        self.0.push_unset_captures(caps);
+    }
+
+    fn has_captures(&self) -> bool {
+        true
+    }
+
+    fn test_pattern(&self, text: &str) -> bool {
+        self.0.test_pattern(text)
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        self.0.first_chars()
+    }
+
+    fn to_regex(&self) -> Option<Regex> {
+        self.0.to_regex()
+    }
+
+    fn read_furthest(&self, text: &str) -> usize {
+        self.0.read_furthest(text)
+    }
+}
+
+impl<T> ReadPattern for NamedCapture<T>
+where
+    T: ReadPattern,
+{
+    fn read_pattern(&self, text: &str) -> Option<usize> {
+        self.1.read_pattern(text)
+    }
+
+    fn read_pattern_all(&self, text: &str) -> Vec<usize> {
+        self.1.read_pattern_all(text)
+    }
+
+    fn read_captures<'t>(&self, text: &'t str, caps: &mut Captures<'t>) -> Option<usize> {
+        read_group(&self.1, Some(self.0), text, caps)
+    }
+
+    fn read_captures_exact<'t>(&self, text: &'t str, len: usize, caps: &mut Captures<'t>) -> bool {
+        read_group_exact(&self.1, Some(self.0), text, len, caps)
+    }
+
+    fn push_unset_captures(&self, caps: &mut Captures) {
+        caps.push_unset(Some(self.0));
+        self.1.push_unset_captures(caps);
+    }
+
+    fn has_captures(&self) -> bool {
+        true
+    }
+
+    fn test_pattern(&self, text: &str) -> bool {
+        self.1.test_pattern(text)
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        self.1.first_chars()
+    }
+
+    fn to_regex(&self) -> Option<Regex> {
+        self.1.to_regex()
+    }
+
+    fn read_furthest(&self, text: &str) -> usize {
+        self.1.read_furthest(text)
     }
 }
 
@@ -22,6 +149,27 @@ pub fn cap<T: ReadPattern>(pattern: T) -> Capture<T> {
     Capture(pattern)
 }
 
+pub fn cap_named<T: ReadPattern>(name: &'static str, pattern: T) -> NamedCapture<T> {
+    NamedCapture(name, pattern)
+}
+
+/// Defines which captures a repetition keeps
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CapturePolicy {
+    /// Keep the captures of every iteration
+    All,
+    /// Keep the captures of the last iteration only
+    Last,
+}
+
+impl CapturePolicy {
+    pub(crate) fn apply(self, caps: &mut Captures, start: usize, iteration: usize) {
+        if self == CapturePolicy::Last {
+            caps.remove(start..iteration);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -30,20 +178,89 @@ mod tests {
     #[test]
     fn capture_str() {
         let pattern = Capture("text");
-        let mut caps = Vec::new();
-
         assert_eq!(pattern.read_pattern("text"), Some("text".len()));
-        assert_eq!(pattern.read_captures("text", &mut caps), Some("text".len()));
-        assert_eq!(caps, ["text"]);
+
+        let caps = pattern.captures("text").unwrap();
+        assert_eq!(caps.to_vec(), ["text"]);
+        assert_eq!(caps.span(0), Some(0.."text".len()));
     }
 
     #[test]
     fn capture_char() {
         let pattern = pat(cap('a'));
-        let mut caps = Vec::new();
-
         assert_eq!(pattern.read_pattern("a"), Some(1));
-        assert_eq!(pattern.read_captures("a", &mut caps), Some(1));
-        assert_eq!(caps, ["a"]);
+
+        let caps = pattern.captures("a").unwrap();
+        assert_eq!(caps.to_vec(), ["a"]);
+        assert!(pattern.has_captures());
+        assert!((pat('b') | pattern).has_captures());
+        assert!(!(pat('b') | 'a').has_captures());
+    }
+
+    #[test]
+    fn capture_nested() {
+        let pattern = cap(pat(cap('a')) & cap('b'));
+        let caps = pattern.captures("ab").unwrap();
+        assert_eq!(caps.as_str(), "ab");
+        assert_eq!(caps.to_vec(), ["ab", "a", "b"]);
+
+        assert_eq!(pattern.captures("aa"), None);
+    }
+
+    #[test]
+    fn capture_named() {
+        let digits = || pat('0'..='9') * (1..);
+        let exp = pat('e') & cap_named("exp", digits());
+        let frac = pat('.') & cap_named("frac", digits());
+        let number = pat(cap_named("int", digits())) & (frac * ..=1) & (exp * ..=1);
+
+        let caps = number.captures("12.5e3").unwrap();
+        assert_eq!(caps.get("int"), Some("12"));
+        assert_eq!(caps.get("frac"), Some("5"));
+        assert_eq!(caps.get("exp"), Some("3"));
+        assert_eq!(caps.span("exp"), Some(5..6));
+
+        // Missing groups are unset and don't shift the others
+        let caps = number.captures("12e3").unwrap();
+        assert_eq!(caps.get("int"), Some("12"));
+        assert_eq!(caps.get("frac"), None);
+        assert_eq!(caps.get("exp"), Some("3"));
+
+        let sign = cap_named("sign", pat('+') | '-');
+        let pattern = (pat(sign) | cap_named("nosign", "")) & cap_named("num", digits());
+        let caps = pattern.captures("-1").unwrap();
+        assert_eq!(caps.get("sign"), Some("-"));
+        assert_eq!(caps.get("num"), Some("1"));
+
+        let caps = pattern.captures("1").unwrap();
+        assert_eq!(caps.get("sign"), None);
+        assert_eq!(caps.get("num"), Some("1"));
+    }
+
+    #[test]
+    fn capture_positions() {
+        // Groups of the other branch are unset, so positions are stable
+        let pattern = (pat(cap('a')) | cap('b')) & cap('c');
+        let caps = pattern.captures("ac").unwrap();
+        assert_eq!(caps.len(), 3);
+        assert_eq!(caps.get(0), Some("a"));
+        assert_eq!(caps.get(1), None);
+        assert_eq!(caps.get(2), Some("c"));
+
+        let caps = pattern.captures("bc").unwrap();
+        assert_eq!(caps.len(), 3);
+        assert_eq!(caps.get(0), None);
+        assert_eq!(caps.get(1), Some("b"));
+        assert_eq!(caps.get(2), Some("c"));
+        assert_eq!(caps.to_vec(), ["b", "c"]);
+
+        let caps = pattern.backtrack().captures("bc").unwrap();
+        assert_eq!(caps.get(1), Some("b"));
+        assert_eq!(caps.get(2), Some("c"));
+
+        // The same name in both branches refers to the matched one
+        let pattern = pat(cap_named("x", 'a')) | cap_named("x", 'b');
+        assert_eq!(pattern.captures("a").unwrap().get("x"), Some("a"));
+        assert_eq!(pattern.captures("b").unwrap().get("x"), Some("b"));
     }
 }
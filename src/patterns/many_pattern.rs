@@ -1,7 +1,66 @@
-use crate::read_pattern::ReadPattern;
+use crate::captures::Captures;
+use crate::first_chars::FirstChars;
+use crate::patterns::CapturePolicy;
+use crate::read_pattern::{push_len, ReadPattern};
+use crate::regex::Regex;
+use std::collections::HashSet;
 
 #[derive(Copy, Clone, Debug)]
-pub struct ManyPattern<T>(pub T, pub u32);
+pub struct ManyPattern<T>(pub T, pub u32, pub CapturePolicy);
+
+impl<T> ManyPattern<T>
+where
+    T: ReadPattern,
+{
+    fn collect_all(
+        &self,
+        text: &str,
+        len: usize,
+        count: u32,
+        visited: &mut HashSet<(usize, u32)>,
+        lens: &mut Vec<usize>,
+    ) {
+        if count == self.1 {
+            push_len(lens, len);
+            return;
+        }
+
+        for l in self.0.read_pattern_all(&text[len..]) {
+            if visited.insert((len + l, count + 1)) {
+                self.collect_all(text, len + l, count + 1, visited, lens);
+            }
+        }
+    }
+
+    fn find_exact(
+        &self,
+        text: &str,
+        target: usize,
+        len: usize,
+        count: u32,
+        visited: &mut HashSet<(usize, u32)>,
+        path: &mut Vec<usize>,
+    ) -> bool {
+        if count == self.1 {
+            return len == target;
+        }
+
+        for l in self.0.read_pattern_all(&text[len..]) {
+            if len + l > target || !visited.insert((len + l, count + 1)) {
+                continue;
+            }
+
+            path.push(l);
+            if self.find_exact(text, target, len + l, count + 1, visited, path) {
+                return true;
+            }
+
+            path.pop();
+        }
+
+        false
+    }
+}
 
 impl<T> ReadPattern for ManyPattern<T>
 where
@@ -19,11 +78,98 @@ where
 
         Some(len)
     }
+
+    fn read_pattern_all(&self, text: &str) -> Vec<usize> {
+        let mut lens = Vec::new();
+        self.collect_all(text, 0, 0, &mut HashSet::new(), &mut lens);
+        lens
+    }
+
+    fn read_captures<'t>(&self, text: &'t str, caps: &mut Captures<'t>) -> Option<usize> {
+        let start = caps.len();
+        let mut len = 0;
+
+        for _ in 0..self.1 {
+            let iteration = caps.len();
+            match self.0.read_captures(&text[len..], caps) {
+                Some(l) => {
+                    self.2.apply(caps, start, iteration);
+                    len += l;
+                }
+                None => {
+                    caps.truncate(start);
+                    return None;
+                }
+            }
+        }
+
+        Some(len)
+    }
+
+    fn read_captures_exact<'t>(&self, text: &'t str, len: usize, caps: &mut Captures<'t>) -> bool {
+        let mut path = Vec::new();
+        if !self.find_exact(text, len, 0, 0, &mut HashSet::new(), &mut path) {
+            return false;
+        }
+
+        let start = caps.len();
+        let mut pos = 0;
+        for l in path {
+            let iteration = caps.len();
+            self.0.read_captures_exact(&text[pos..], l, caps);
+            self.2.apply(caps, start, iteration);
+            pos += l;
+        }
+
+        true
+    }
+
+    fn push_unset_captures(&self, caps: &mut Captures) {
+        let count = match self.2 {
+            CapturePolicy::All => self.1,
+            CapturePolicy::Last => self.1.min(1),
+        };
+
+        for _ in 0..count {
+            self.0.push_unset_captures(caps);
+        }
+    }
+
+    fn has_captures(&self) -> bool {
+        self.0.has_captures()
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        if self.1 == 0 {
+            FirstChars::empty()
+        } else {
+            self.0.first_chars()
+        }
+    }
+
+    fn to_regex(&self) -> Option<Regex> {
+        let regex = self.0.to_regex()?;
+        Some(Regex::Repeat(Box::new(regex), self.1, Some(self.1)))
+    }
+
+    fn read_furthest(&self, text: &str) -> usize {
+        let mut len = 0;
+
+        for _ in 0..self.1 {
+            let rest = &text[len..];
+            match self.0.read_pattern(rest) {
+                Some(l) => len += l,
+                None => return len + self.0.read_furthest(rest),
+            }
+        }
+
+        len
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::super::pat;
+    use super::super::{cap, pat};
     use crate::ReadPattern;
 
     #[test]
@@ -47,4 +193,27 @@ mod tests {
         assert!(pattern.test_pattern("zzzz"));
         assert!(!pattern.test_pattern("zzzzz"));
     }
+
+    #[test]
+    fn many_pattern_all() {
+        let pattern = (pat("ab") | "a") * 2;
+        assert_eq!(pattern.read_pattern_all("abab"), [4, 3]);
+        assert_eq!(pattern.read_pattern_all("aab"), [3, 2]);
+        assert_eq!(pattern.read_pattern_all("ab"), []);
+    }
+
+    #[test]
+    fn many_pattern_caps() {
+        let pattern = pat(cap('0'..='9')) * 3;
+        let caps = pattern.captures("123").unwrap();
+        assert_eq!(caps.as_str(), "123");
+        assert_eq!(caps.to_vec(), ["1", "2", "3"]);
+
+        assert_eq!(pattern.captures("12"), None);
+
+        let pattern = (pat(cap('0'..='9')) * 3).last_captures();
+        let caps = pattern.captures("123").unwrap();
+        assert_eq!(caps.as_str(), "123");
+        assert_eq!(caps.to_vec(), ["3"]);
+    }
 }
@@ -1,4 +1,7 @@
-use crate::read_pattern::ReadPattern;
+use crate::captures::Captures;
+use crate::first_chars::FirstChars;
+use crate::read_pattern::{push_len, ReadPattern};
+use crate::regex::Regex;
 
 #[derive(Copy, Clone, Debug)]
 pub struct OrPattern<L, R>(pub L, pub R);
@@ -14,15 +17,63 @@ where
             .or_else(|| self.1.read_pattern(text))
     }
 
-    fn read_captures<'t>(&self, text: &'t str, buf: &mut Vec<&'t str>) -> Option<usize> {
-        let mut add = Vec::new();
-        let result = match self.0.read_captures(text, &mut add) {
-            None => self.1.read_captures(text, &mut add),
+    fn read_pattern_all(&self, text: &str) -> Vec<usize> {
+        let mut lens = self.0.read_pattern_all(text);
+        for len in self.1.read_pattern_all(text) {
+            push_len(&mut lens, len);
+        }
+
+        lens
+    }
+
+    fn read_captures<'t>(&self, text: &'t str, caps: &mut Captures<'t>) -> Option<usize> {
+        let start = caps.len();
+        if let Some(len) = self.0.read_captures(text, caps) {
+            self.1.push_unset_captures(caps);
+            return Some(len);
+        }
+
+        caps.truncate(start);
+        self.0.push_unset_captures(caps);
+        match self.1.read_captures(text, caps) {
+            None => {
+                caps.truncate(start);
+                None
+            }
             s => s,
-        };
+        }
+    }
 
-        buf.append(&mut add);
-        result
+    fn read_captures_exact<'t>(&self, text: &'t str, len: usize, caps: &mut Captures<'t>) -> bool {
+        if self.0.read_pattern_all(text).contains(&len) {
+            self.0.read_captures_exact(text, len, caps);
+            self.1.push_unset_captures(caps);
+            true
+        } else {
+            self.0.push_unset_captures(caps);
+            self.1.read_captures_exact(text, len, caps)
+        }
+    }
+
+    fn push_unset_captures(&self, caps: &mut Captures) {
+        self.0.push_unset_captures(caps);
+        self.1.push_unset_captures(caps);
+    }
+
+    fn has_captures(&self) -> bool {
+        self.0.has_captures() || self.1.has_captures()
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        self.0.first_chars().union(self.1.first_chars())
+    }
+
+    fn to_regex(&self) -> Option<Regex> {
+        Some(Regex::Alt(vec![self.0.to_regex()?, self.1.to_regex()?]))
+    }
+
+    fn read_furthest(&self, text: &str) -> usize {
+        self.0.read_furthest(text).max(self.1.read_furthest(text))
     }
 }
 
@@ -61,10 +112,25 @@ mod tests {
     #[test]
     fn or_pattern_caps() {
         let pattern = pat(cap(Pattern("foo"))) | cap("bar");
-        let mut caps = Vec::new();
+        assert_eq!(pattern.captures("foo").unwrap().to_vec(), ["foo"]);
+        assert_eq!(pattern.captures("bar").unwrap().to_vec(), ["bar"]);
+        assert_eq!(pattern.captures("baz"), None);
+
+        // Captures of the failed branch are dropped
+        let pattern = (pat(cap("a")) & "b") | cap("ac");
+        let caps = pattern.captures("ac").unwrap();
+        assert_eq!(caps.as_str(), "ac");
+        assert_eq!(caps.to_vec(), ["ac"]);
+    }
+
+    #[test]
+    fn or_pattern_all() {
+        let pattern = Pattern("") | "b";
+        assert_eq!(pattern.read_pattern_all("b"), [0, 1]);
+        assert_eq!(pattern.read_pattern_all("a"), [0]);
 
-        assert_eq!(pattern.read_captures("foo", &mut caps), Some(3));
-        assert_eq!(pattern.read_captures("bar", &mut caps), Some(3));
-        assert_eq!(caps, ["foo", "bar"]);
+        let pattern = Pattern("a") | "ab" | "a";
+        assert_eq!(pattern.read_pattern_all("ab"), [1, 2]);
+        assert_eq!(pattern.read_pattern_all("b"), []);
     }
 }
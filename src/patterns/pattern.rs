@@ -1,15 +1,42 @@
-use crate::patterns::{AndPattern, ManyPattern, OrPattern, RangePattern, UntilPattern};
+use crate::captures::Captures;
+use crate::first_chars::FirstChars;
+use crate::patterns::{
+    AndPattern, BacktrackPattern, CapturePolicy, ManyPattern, OrPattern, RangePattern, UntilPattern,
+};
 use crate::read_pattern::ReadPattern;
+use crate::regex::Regex;
 use std::ops::{
     BitAnd, BitOr, Mul, Range, RangeFrom, RangeFull, RangeInclusive, RangeTo, RangeToInclusive,
 };
 
+/// Wrapper of patterns to combine them with operators
+///
+/// Patterns of text, bytes and tokens share it, as `pat`, `bpat` and `tpat` make it.
+///
 #[derive(Copy, Clone, Debug)]
 pub struct Pattern<T>(pub T);
 
-impl<T: ReadPattern> Pattern<T> {
-    pub fn until<U: ReadPattern>(self, pattern: U) -> UntilPattern<T, U> {
-        UntilPattern(self.0, pattern)
+impl<T> Pattern<T> {
+    pub fn until<U>(self, pattern: U) -> UntilPattern<T, U> {
+        UntilPattern(self.0, pattern, CapturePolicy::All)
+    }
+
+    pub fn backtrack(self) -> Pattern<BacktrackPattern<T>> {
+        Pattern(BacktrackPattern(self.0))
+    }
+}
+
+impl<T> Pattern<ManyPattern<T>> {
+    pub fn last_captures(self) -> Self {
+        let ManyPattern(pattern, n, _) = self.0;
+        Pattern(ManyPattern(pattern, n, CapturePolicy::Last))
+    }
+}
+
+impl<T, R> Pattern<RangePattern<T, R>> {
+    pub fn last_captures(self) -> Self {
+        let RangePattern(pattern, range, _) = self.0;
+        Pattern(RangePattern(pattern, range, CapturePolicy::Last))
     }
 }
 
@@ -21,8 +48,40 @@ where
         self.0.read_pattern(text)
     }
 
-    fn read_captures<'t>(&self, text: &'t str, buf: &mut Vec<&'t str>) -> Option<usize> {
-        self.0.read_captures(text, buf)
+    fn read_pattern_all(&self, text: &str) -> Vec<usize> {
+        self.0.read_pattern_all(text)
+    }
+
+    fn read_captures<'t>(&self, text: &'t str, caps: &mut Captures<'t>) -> Option<usize> {
+        self.0.read_captures(text, caps)
+    }
+
+    fn read_captures_exact<'t>(&self, text: &'t str, len: usize, caps: &mut Captures<'t>) -> bool {
+        self.0.read_captures_exact(text, len, caps)
+    }
+
+    fn push_unset_captures(&self, caps: &mut Captures) {
+        self.0.push_unset_captures(caps);
+    }
+
+    fn test_pattern(&self, text: &str) -> bool {
+        self.0.test_pattern(text)
+    }
+
+    fn has_captures(&self) -> bool {
+        self.0.has_captures()
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        self.0.first_chars()
+    }
+
+    fn to_regex(&self) -> Option<Regex> {
+        self.0.to_regex()
+    }
+
+    fn read_furthest(&self, text: &str) -> usize {
+        self.0.read_furthest(text)
     }
 }
 
@@ -30,11 +89,7 @@ pub fn pat<T: ReadPattern>(pattern: T) -> Pattern<T> {
     Pattern(pattern)
 }
 
-impl<L, R> BitOr<R> for Pattern<L>
-where
-    L: ReadPattern,
-    R: ReadPattern,
-{
+impl<L, R> BitOr<R> for Pattern<L> {
     type Output = Pattern<OrPattern<L, R>>;
 
     fn bitor(self, rhs: R) -> Self::Output {
@@ -42,11 +97,7 @@ where
     }
 }
 
-impl<L, R> BitAnd<R> for Pattern<L>
-where
-    L: ReadPattern,
-    R: ReadPattern,
-{
+impl<L, R> BitAnd<R> for Pattern<L> {
     type Output = Pattern<AndPattern<L, R>>;
 
     fn bitand(self, rhs: R) -> Self::Output {
@@ -54,80 +105,59 @@ where
     }
 }
 
-impl<T> Mul<u32> for Pattern<T>
-where
-    T: ReadPattern,
-{
+impl<T> Mul<u32> for Pattern<T> {
     type Output = Pattern<ManyPattern<T>>;
 
     fn mul(self, rhs: u32) -> Self::Output {
-        Pattern(ManyPattern(self.0, rhs))
+        Pattern(ManyPattern(self.0, rhs, CapturePolicy::All))
     }
 }
 
-impl<T> Mul<RangeFull> for Pattern<T>
-where
-    T: ReadPattern,
-{
+impl<T> Mul<RangeFull> for Pattern<T> {
     type Output = Pattern<RangePattern<T, RangeFull>>;
 
     fn mul(self, rhs: RangeFull) -> Self::Output {
-        Pattern(RangePattern(self.0, rhs))
+        Pattern(RangePattern(self.0, rhs, CapturePolicy::All))
     }
 }
 
-impl<T> Mul<RangeFrom<u32>> for Pattern<T>
-where
-    T: ReadPattern,
-{
+impl<T> Mul<RangeFrom<u32>> for Pattern<T> {
     type Output = Pattern<RangePattern<T, RangeFrom<u32>>>;
 
     fn mul(self, rhs: RangeFrom<u32>) -> Self::Output {
-        Pattern(RangePattern(self.0, rhs))
+        Pattern(RangePattern(self.0, rhs, CapturePolicy::All))
     }
 }
 
-impl<T> Mul<RangeTo<u32>> for Pattern<T>
-where
-    T: ReadPattern,
-{
+impl<T> Mul<RangeTo<u32>> for Pattern<T> {
     type Output = Pattern<RangePattern<T, RangeTo<u32>>>;
 
     fn mul(self, rhs: RangeTo<u32>) -> Self::Output {
-        Pattern(RangePattern(self.0, rhs))
+        Pattern(RangePattern(self.0, rhs, CapturePolicy::All))
     }
 }
 
-impl<T> Mul<RangeToInclusive<u32>> for Pattern<T>
-where
-    T: ReadPattern,
-{
+impl<T> Mul<RangeToInclusive<u32>> for Pattern<T> {
     type Output = Pattern<RangePattern<T, RangeToInclusive<u32>>>;
 
     fn mul(self, rhs: RangeToInclusive<u32>) -> Self::Output {
-        Pattern(RangePattern(self.0, rhs))
+        Pattern(RangePattern(self.0, rhs, CapturePolicy::All))
     }
 }
 
-impl<T> Mul<Range<u32>> for Pattern<T>
-where
-    T: ReadPattern,
-{
+impl<T> Mul<Range<u32>> for Pattern<T> {
     type Output = Pattern<RangePattern<T, Range<u32>>>;
 
     fn mul(self, rhs: Range<u32>) -> Self::Output {
-        Pattern(RangePattern(self.0, rhs))
+        Pattern(RangePattern(self.0, rhs, CapturePolicy::All))
     }
 }
 
-impl<T> Mul<RangeInclusive<u32>> for Pattern<T>
-where
-    T: ReadPattern,
-{
+impl<T> Mul<RangeInclusive<u32>> for Pattern<T> {
     type Output = Pattern<RangePattern<T, RangeInclusive<u32>>>;
 
     fn mul(self, rhs: RangeInclusive<u32>) -> Self::Output {
-        Pattern(RangePattern(self.0, rhs))
+        Pattern(RangePattern(self.0, rhs, CapturePolicy::All))
     }
 }
 
@@ -1,8 +1,107 @@
-use crate::read_pattern::ReadPattern;
+use crate::captures::Captures;
+use crate::first_chars::FirstChars;
+use crate::patterns::CapturePolicy;
+use crate::read_pattern::{push_len, ReadPattern};
+use crate::regex::Regex;
+use std::collections::HashSet;
 use std::ops::{Bound, RangeBounds};
 
 #[derive(Copy, Clone, Debug)]
-pub struct RangePattern<T, R>(pub T, pub R);
+pub struct RangePattern<T, R>(pub T, pub R, pub CapturePolicy);
+
+impl<T, R> RangePattern<T, R>
+where
+    R: RangeBounds<u32>,
+{
+    pub(crate) fn can_repeat(&self, count: u32) -> bool {
+        match self.1.end_bound() {
+            Bound::Included(b) => count < *b,
+            Bound::Excluded(b) => count + 1 < *b,
+            Bound::Unbounded => true,
+        }
+    }
+
+    pub(crate) fn next_count(&self, count: u32) -> u32 {
+        // With no upper bound the states above the minimum are all the same
+        match (self.1.start_bound(), self.1.end_bound()) {
+            (Bound::Included(b), Bound::Unbounded) => (count + 1).min(*b),
+            (Bound::Excluded(b), Bound::Unbounded) => (count + 1).min(*b + 1),
+            (Bound::Unbounded, Bound::Unbounded) => 0,
+            _ => count + 1,
+        }
+    }
+}
+
+impl<T, R> RangePattern<T, R>
+where
+    T: ReadPattern,
+    R: RangeBounds<u32>,
+{
+    fn collect_all(
+        &self,
+        text: &str,
+        len: usize,
+        count: u32,
+        visited: &mut HashSet<(usize, u32)>,
+        lens: &mut Vec<usize>,
+    ) {
+        if self.can_repeat(count) {
+            for l in self.0.read_pattern_all(&text[len..]) {
+                // An empty repetition only makes sense to reach the minimum count
+                if l == 0 && self.1.contains(&count) {
+                    continue;
+                }
+
+                let next = self.next_count(count);
+                if visited.insert((len + l, next)) {
+                    self.collect_all(text, len + l, next, visited, lens);
+                }
+            }
+        }
+
+        if self.1.contains(&count) {
+            push_len(lens, len);
+        }
+    }
+
+    fn find_exact(
+        &self,
+        text: &str,
+        target: usize,
+        len: usize,
+        count: u32,
+        visited: &mut HashSet<(usize, u32)>,
+        path: &mut Vec<usize>,
+    ) -> bool {
+        if len == target && self.1.contains(&count) {
+            return true;
+        }
+
+        if !self.can_repeat(count) {
+            return false;
+        }
+
+        for l in self.0.read_pattern_all(&text[len..]) {
+            if len + l > target || (l == 0 && self.1.contains(&count)) {
+                continue;
+            }
+
+            let next = self.next_count(count);
+            if !visited.insert((len + l, next)) {
+                continue;
+            }
+
+            path.push(l);
+            if self.find_exact(text, target, len + l, next, visited, path) {
+                return true;
+            }
+
+            path.pop();
+        }
+
+        false
+    }
+}
 
 impl<T, R> ReadPattern for RangePattern<T, R>
 where
@@ -34,11 +133,140 @@ where
             }
         }
     }
+
+    fn read_pattern_all(&self, text: &str) -> Vec<usize> {
+        let mut lens = Vec::new();
+        self.collect_all(text, 0, 0, &mut HashSet::new(), &mut lens);
+        lens
+    }
+
+    fn read_captures<'t>(&self, text: &'t str, caps: &mut Captures<'t>) -> Option<usize> {
+        if self.0.read_pattern("").is_some() && self.1.end_bound() == Bound::Unbounded {
+            panic!("Infinity loop")
+        }
+
+        let start = caps.len();
+        let mut len = 0;
+        let mut count = 0;
+
+        loop {
+            let iteration = caps.len();
+            match self.0.read_captures(&text[len..], caps) {
+                Some(l) => {
+                    self.2.apply(caps, start, iteration);
+                    len += l;
+                    count += 1;
+
+                    match self.1.end_bound() {
+                        Bound::Included(b) if *b == count => return Some(len),
+                        Bound::Excluded(b) if *b == count + 1 => return Some(len),
+                        _ => {}
+                    }
+                }
+                None if self.1.contains(&count) => {
+                    caps.truncate(iteration);
+                    return Some(len);
+                }
+                None => {
+                    caps.truncate(start);
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn read_captures_exact<'t>(&self, text: &'t str, len: usize, caps: &mut Captures<'t>) -> bool {
+        let mut path = Vec::new();
+        if !self.find_exact(text, len, 0, 0, &mut HashSet::new(), &mut path) {
+            return false;
+        }
+
+        let start = caps.len();
+        let mut pos = 0;
+        for l in path {
+            let iteration = caps.len();
+            self.0.read_captures_exact(&text[pos..], l, caps);
+            self.2.apply(caps, start, iteration);
+            pos += l;
+        }
+
+        true
+    }
+
+    fn push_unset_captures(&self, caps: &mut Captures) {
+        // Only the required iterations are reserved
+        let min = match self.1.start_bound() {
+            Bound::Included(b) => *b,
+            Bound::Excluded(b) => *b + 1,
+            Bound::Unbounded => 0,
+        };
+
+        let count = match self.2 {
+            CapturePolicy::All => min,
+            CapturePolicy::Last => min.min(1),
+        };
+
+        for _ in 0..count {
+            self.0.push_unset_captures(caps);
+        }
+    }
+
+    fn has_captures(&self) -> bool {
+        self.0.has_captures()
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        if self.1.contains(&0) {
+            self.0.first_chars().or_empty()
+        } else {
+            self.0.first_chars()
+        }
+    }
+
+    fn to_regex(&self) -> Option<Regex> {
+        let regex = self.0.to_regex()?;
+        let min = match self.1.start_bound() {
+            Bound::Included(b) => *b,
+            Bound::Excluded(b) => *b + 1,
+            Bound::Unbounded => 0,
+        };
+
+        let max = match self.1.end_bound() {
+            Bound::Included(b) => Some(*b),
+            Bound::Excluded(0) => return Some(Regex::never()),
+            Bound::Excluded(b) => Some(*b - 1),
+            Bound::Unbounded => None,
+        };
+
+        match max {
+            Some(max) if max < min => Some(Regex::never()),
+            _ => Some(Regex::Repeat(Box::new(regex), min, max)),
+        }
+    }
+
+    fn read_furthest(&self, text: &str) -> usize {
+        let mut len = 0;
+        let mut count = 0;
+
+        while self.can_repeat(count) {
+            let rest = &text[len..];
+            match self.0.read_pattern(rest) {
+                Some(0) => break,
+                Some(l) => {
+                    len += l;
+                    count += 1;
+                }
+                None => return len + self.0.read_furthest(rest),
+            }
+        }
+
+        len
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::super::pat;
+    use super::super::{cap, pat};
     use crate::ReadPattern;
 
     #[test]
@@ -78,4 +306,39 @@ mod tests {
         assert!(range_inclusive.test_pattern("ff"));
         assert!(!range_inclusive.test_pattern("fff"));
     }
+
+    #[test]
+    fn range_pattern_all() {
+        let full = pat("a") * ..;
+        assert_eq!(full.read_pattern_all("aaa"), [3, 2, 1, 0]);
+        assert_eq!(full.read_pattern_all("b"), [0]);
+
+        let range = pat("b") * (1..=2);
+        assert_eq!(range.read_pattern_all("bbb"), [2, 1]);
+        assert_eq!(range.read_pattern_all(""), []);
+
+        let empty = (pat("") | "c") * (2..);
+        assert_eq!(empty.read_pattern_all("cc"), [2, 1, 0]);
+    }
+
+    #[test]
+    fn range_pattern_caps() {
+        let digits = pat(cap('0'..='9')) * (1..);
+        let caps = digits.captures("42").unwrap();
+        assert_eq!(caps.as_str(), "42");
+        assert_eq!(caps.to_vec(), ["4", "2"]);
+
+        assert_eq!(digits.captures("x"), None);
+
+        // The failed iteration doesn't leave its captures
+        let pairs = (pat(cap('a')) & 'b') * ..;
+        let caps = pairs.captures("ababa").unwrap();
+        assert_eq!(caps.as_str(), "abab");
+        assert_eq!(caps.to_vec(), ["a", "a"]);
+
+        let last = (pat(cap('0'..='9')) * (1..)).last_captures();
+        let caps = last.captures("123").unwrap();
+        assert_eq!(caps.as_str(), "123");
+        assert_eq!(caps.to_vec(), ["3"]);
+    }
 }
@@ -1,7 +1,75 @@
-use crate::read_pattern::ReadPattern;
+use crate::captures::Captures;
+use crate::first_chars::FirstChars;
+use crate::patterns::CapturePolicy;
+use crate::read_pattern::{push_len, ReadPattern};
+use std::collections::HashSet;
 
 #[derive(Copy, Clone, Debug)]
-pub struct UntilPattern<P, U>(pub P, pub U);
+pub struct UntilPattern<P, U>(pub P, pub U, pub CapturePolicy);
+
+impl<P, U> UntilPattern<P, U>
+where
+    P: ReadPattern,
+    U: ReadPattern,
+{
+    pub fn last_captures(self) -> Self {
+        UntilPattern(self.0, self.1, CapturePolicy::Last)
+    }
+
+    fn collect_all(
+        &self,
+        text: &str,
+        len: usize,
+        visited: &mut HashSet<usize>,
+        lens: &mut Vec<usize>,
+    ) {
+        let rest = &text[len..];
+        let ends = self.1.read_pattern_all(rest);
+        if !ends.is_empty() {
+            for end_len in ends {
+                push_len(lens, len + end_len);
+            }
+
+            return;
+        }
+
+        for l in self.0.read_pattern_all(rest) {
+            if l != 0 && visited.insert(len + l) {
+                self.collect_all(text, len + l, visited, lens);
+            }
+        }
+    }
+
+    fn find_exact(
+        &self,
+        text: &str,
+        target: usize,
+        len: usize,
+        visited: &mut HashSet<usize>,
+        path: &mut Vec<usize>,
+    ) -> bool {
+        let rest = &text[len..];
+        let ends = self.1.read_pattern_all(rest);
+        if !ends.is_empty() {
+            return ends.contains(&(target - len));
+        }
+
+        for l in self.0.read_pattern_all(rest) {
+            if l == 0 || len + l > target || !visited.insert(len + l) {
+                continue;
+            }
+
+            path.push(l);
+            if self.find_exact(text, target, len + l, visited, path) {
+                return true;
+            }
+
+            path.pop();
+        }
+
+        false
+    }
+}
 
 impl<P, U> ReadPattern for UntilPattern<P, U>
 where
@@ -19,11 +87,95 @@ where
             }
         }
     }
+
+    fn read_pattern_all(&self, text: &str) -> Vec<usize> {
+        let mut lens = Vec::new();
+        self.collect_all(text, 0, &mut HashSet::new(), &mut lens);
+        lens
+    }
+
+    fn read_captures<'t>(&self, text: &'t str, caps: &mut Captures<'t>) -> Option<usize> {
+        let start = caps.len();
+        let mut len = 0;
+
+        loop {
+            let rest = &text[len..];
+            let iteration = caps.len();
+            if let Some(end_len) = self.1.read_captures(rest, caps) {
+                break Some(len + end_len);
+            }
+
+            caps.truncate(iteration);
+            match self.0.read_captures(rest, caps) {
+                Some(l) => {
+                    self.2.apply(caps, start, iteration);
+                    len += l;
+                }
+                None => {
+                    caps.truncate(start);
+                    break None;
+                }
+            }
+        }
+    }
+
+    fn read_captures_exact<'t>(&self, text: &'t str, len: usize, caps: &mut Captures<'t>) -> bool {
+        let mut path = Vec::new();
+        if !self.find_exact(text, len, 0, &mut HashSet::new(), &mut path) {
+            return false;
+        }
+
+        let start = caps.len();
+        let mut pos = 0;
+        for l in path {
+            let iteration = caps.len();
+            self.0.read_captures_exact(&text[pos..], l, caps);
+            self.2.apply(caps, start, iteration);
+            pos += l;
+        }
+
+        self.1.read_captures_exact(&text[pos..], len - pos, caps)
+    }
+
+    fn push_unset_captures(&self, caps: &mut Captures) {
+        // The repeated pattern may match no times, but the end always matches
+        self.1.push_unset_captures(caps);
+    }
+
+    fn has_captures(&self) -> bool {
+        self.0.has_captures() || self.1.has_captures()
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        // The end pattern is tried first at every position
+        let first = self.1.first_chars();
+        if first.is_nullable() {
+            first
+        } else {
+            first.union(self.0.first_chars())
+        }
+    }
+
+    fn read_furthest(&self, text: &str) -> usize {
+        let mut len = 0;
+
+        loop {
+            let rest = &text[len..];
+            if let Some(end_len) = self.1.read_pattern(rest) {
+                break len + end_len;
+            }
+
+            match self.0.read_pattern(rest) {
+                Some(l) if l != 0 => len += l,
+                _ => break len + self.0.read_furthest(rest).max(self.1.read_furthest(rest)),
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::super::pat;
+    use super::super::{cap, pat};
     use crate::patterns::ANY;
     use crate::ReadPattern;
 
@@ -50,6 +202,11 @@ mod tests {
         let slashed_text = pat('/') & pat(ANY) & pat(ANY).until('/');
         assert!(!slashed_text.test_pattern("//")); // It fails
         assert!(slashed_text.test_pattern("/a/")); // And it passes
+
+        // Another way is to turn on backtracking,
+        // then the pattern behaves like a regular expression:
+        let slashed_text = (pat('/') & (pat(ANY) * ..) & '/').backtrack();
+        assert!(slashed_text.test_pattern("/test/")); // Passes too
     }
 
     #[test]
@@ -71,4 +228,44 @@ mod tests {
         assert!(!parentheses_bin.test_pattern("("));
         assert!(!parentheses_bin.test_pattern(")"));
     }
+
+    #[test]
+    fn until_pattern_all() {
+        let p = (pat("ab") | "a").until("b!");
+        assert_eq!(p.read_pattern("ab!"), None);
+        assert_eq!(p.read_pattern_all("ab!"), [3]);
+        assert_eq!(p.read_pattern_all("b!"), [2]);
+        assert_eq!(p.read_pattern_all("ab"), []);
+    }
+
+    #[test]
+    fn until_pattern_caps() {
+        let p = pat(cap(ANY)).until(cap(";"));
+        let caps = p.captures("ab;").unwrap();
+        assert_eq!(caps.as_str(), "ab;");
+        assert_eq!(caps.to_vec(), ["a", "b", ";"]);
+
+        let p = pat(cap(ANY)).until(cap(";")).last_captures();
+        let caps = p.captures("ab;").unwrap();
+        assert_eq!(caps.as_str(), "ab;");
+        assert_eq!(caps.to_vec(), ["b", ";"]);
+
+        assert_eq!(p.captures("ab"), None);
+    }
+
+    #[test]
+    fn until_pattern_furthest() {
+        let comment = pat("/*") & pat(ANY).until("*/");
+        assert_eq!(comment.read_furthest("/* a */ b"), 7);
+        assert_eq!(comment.read_furthest("/* a *"), 6);
+        assert_eq!(comment.read_furthest("/"), 1);
+
+        let p = pat('a').until(";");
+        assert_eq!(p.read_furthest("aab"), 2);
+
+        let number = pat("0x") & ((pat('0'..='9') | ('a'..='f')) * (1..3));
+        assert_eq!(number.read_furthest("0xz"), 2);
+        assert_eq!(number.read_furthest("0xfff"), 4);
+        assert_eq!((pat('a') * 3).read_furthest("aab"), 2);
+    }
 }
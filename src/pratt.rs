@@ -0,0 +1,503 @@
+use crate::cursor::TokenCursor;
+use crate::parse::{ParseResult, ParseWith};
+use crate::span::Span;
+use std::error::Error;
+use std::fmt;
+
+/// Source of tokens for the `Pratt` parser
+///
+/// It's implemented for token slices and `TokenCursor`,
+/// which wraps a `ParseIterator`.
+///
+pub trait TokenSource {
+    type Token;
+    /// Item of the source, which errors report as found
+    type Item: Clone;
+
+    /// Returns the next item, `None` at the end
+    fn peek_item(&mut self) -> Option<&Self::Item>;
+
+    /// Returns the next token, `None` at the end or at an item which isn't a token
+    fn peek_token(&mut self) -> Option<&Self::Token>;
+
+    /// Consumes the next token
+    fn next_token(&mut self) -> Option<Self::Token>;
+
+    /// Span of the next item, `None` if tokens have no spans
+    fn peek_span(&mut self) -> Option<Span> {
+        None
+    }
+}
+
+impl<T: Clone> TokenSource for &[T] {
+    type Token = T;
+    type Item = T;
+
+    fn peek_item(&mut self) -> Option<&T> {
+        self.first()
+    }
+
+    fn peek_token(&mut self) -> Option<&T> {
+        self.first()
+    }
+
+    fn next_token(&mut self) -> Option<T> {
+        let (tok, rest) = self.split_first()?;
+        *self = rest;
+        Some(tok.clone())
+    }
+}
+
+impl<'p, 't, P, C> TokenSource for TokenCursor<'p, 't, P, C>
+where
+    P: ParseWith<'t, C>,
+    P::Token: Clone,
+{
+    type Token = P::Token;
+    type Item = ParseResult<P::Token>;
+
+    fn peek_item(&mut self) -> Option<&ParseResult<P::Token>> {
+        self.peek()
+    }
+
+    fn peek_token(&mut self) -> Option<&P::Token> {
+        match self.peek() {
+            Some(ParseResult::Ok(tok, _)) => Some(tok),
+            _ => None,
+        }
+    }
+
+    fn next_token(&mut self) -> Option<P::Token> {
+        match self.advance() {
+            Some(ParseResult::Ok(tok, _)) => Some(tok.clone()),
+            _ => None,
+        }
+    }
+
+    fn peek_span(&mut self) -> Option<Span> {
+        Some(self.next_span())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Assoc {
+    Left,
+    Right,
+}
+
+type Atom<'a, T, E> = Box<dyn Fn(&T) -> Option<E> + 'a>;
+type Prefix<'a, T, E> = Box<dyn Fn(T, E) -> E + 'a>;
+type Infix<'a, T, E> = Box<dyn Fn(E, T, E) -> E + 'a>;
+type Postfix<'a, T, E> = Box<dyn Fn(E, T) -> E + 'a>;
+
+/// Operator precedence parser of expressions
+///
+/// Operators are tokens with precedences, where the higher one binds tighter.
+/// The operand of a prefix operator takes infix operators of the same
+/// precedence only if they're right-associative, so `-a ^ b` is `-(a ^ b)`,
+/// while `-a * b` is `(-a) * b`. Callbacks build the expression of operands,
+/// and may borrow for the lifetime `'a`.
+///
+pub struct Pratt<'a, T, E> {
+    atom: Atom<'a, T, E>,
+    prefix: Vec<(T, u32, Prefix<'a, T, E>)>,
+    infix: Vec<(T, u32, Assoc, Infix<'a, T, E>)>,
+    postfix: Vec<(T, u32, Postfix<'a, T, E>)>,
+    groups: Vec<(T, T)>,
+}
+
+impl<'a, T, E> Pratt<'a, T, E>
+where
+    T: PartialEq + Clone,
+{
+    /// Creates the parser, which converts tokens into operands with the `atom`
+    pub fn new<A>(atom: A) -> Self
+    where
+        A: Fn(&T) -> Option<E> + 'a,
+    {
+        Pratt {
+            atom: Box::new(atom),
+            prefix: Vec::new(),
+            infix: Vec::new(),
+            postfix: Vec::new(),
+            groups: Vec::new(),
+        }
+    }
+
+    pub fn prefix<F>(mut self, op: T, precedence: u32, f: F) -> Self
+    where
+        F: Fn(T, E) -> E + 'a,
+    {
+        self.prefix.push((op, precedence, Box::new(f)));
+        self
+    }
+
+    pub fn infix<F>(mut self, op: T, precedence: u32, assoc: Assoc, f: F) -> Self
+    where
+        F: Fn(E, T, E) -> E + 'a,
+    {
+        self.infix.push((op, precedence, assoc, Box::new(f)));
+        self
+    }
+
+    pub fn postfix<F>(mut self, op: T, precedence: u32, f: F) -> Self
+    where
+        F: Fn(E, T) -> E + 'a,
+    {
+        self.postfix.push((op, precedence, Box::new(f)));
+        self
+    }
+
+    /// Adds brackets, which group an expression
+    pub fn group(mut self, open: T, close: T) -> Self {
+        self.groups.push((open, close));
+        self
+    }
+
+    /// Parses the expression and stops at the first token which doesn't continue it
+    ///
+    /// On errors the unexpected token is left in the source.
+    pub fn parse<S>(&self, source: &mut S) -> Result<E, PrattError<T, S::Item>>
+    where
+        S: TokenSource<Token = T>,
+    {
+        self.parse_bp(source, 0)
+    }
+
+    /// Parses operands and operators, which bind at least as tight as `min_bp`
+    fn parse_bp<S>(&self, source: &mut S, min_bp: u32) -> Result<E, PrattError<T, S::Item>>
+    where
+        S: TokenSource<Token = T>,
+    {
+        let mut lhs = self.parse_operand(source)?;
+
+        while let Some(tok) = source.peek_token() {
+            if let Some(idx) = self.postfix.iter().position(|(op, ..)| op == tok) {
+                let (_, precedence, f) = &self.postfix[idx];
+                if 2 * precedence < min_bp {
+                    break;
+                }
+
+                let tok = source.next_token().expect("peeked token");
+                lhs = f(lhs, tok);
+                continue;
+            }
+
+            if let Some(idx) = self.infix.iter().position(|(op, ..)| op == tok) {
+                let (_, precedence, assoc, f) = &self.infix[idx];
+                let (left_bp, right_bp) = match assoc {
+                    Assoc::Left => (2 * precedence, 2 * precedence + 1),
+                    Assoc::Right => (2 * precedence + 1, 2 * precedence),
+                };
+
+                if left_bp < min_bp {
+                    break;
+                }
+
+                let tok = source.next_token().expect("peeked token");
+                let rhs = self.parse_bp(source, right_bp)?;
+                lhs = f(lhs, tok, rhs);
+                continue;
+            }
+
+            break;
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_operand<S>(&self, source: &mut S) -> Result<E, PrattError<T, S::Item>>
+    where
+        S: TokenSource<Token = T>,
+    {
+        let span = source.peek_span();
+        let tok = match source.peek_token() {
+            Some(tok) => tok,
+            None => {
+                let found = source.peek_item().cloned();
+                return Err(PrattError::new(None, found, span));
+            }
+        };
+
+        if let Some(idx) = self.prefix.iter().position(|(op, ..)| op == tok) {
+            let (_, precedence, f) = &self.prefix[idx];
+            let tok = source.next_token().expect("peeked token");
+            let operand = self.parse_bp(source, 2 * precedence + 1)?;
+            return Ok(f(tok, operand));
+        }
+
+        if let Some(idx) = self.groups.iter().position(|(open, _)| open == tok) {
+            source.next_token();
+            let expr = self.parse_bp(source, 0)?;
+
+            let close = &self.groups[idx].1;
+            let span = source.peek_span();
+            return match source.peek_token() {
+                Some(tok) if tok == close => {
+                    source.next_token();
+                    Ok(expr)
+                }
+                _ => {
+                    let found = source.peek_item().cloned();
+                    Err(PrattError::new(Some(close.clone()), found, span))
+                }
+            };
+        }
+
+        match (self.atom)(tok) {
+            Some(expr) => {
+                source.next_token();
+                Ok(expr)
+            }
+            None => {
+                let found = source.peek_item().cloned();
+                Err(PrattError::new(None, found, span))
+            }
+        }
+    }
+}
+
+/// Error of the `Pratt` parser
+///
+/// The found item is a token of slices, and a `ParseResult` of cursors,
+/// so errors of the lexer are reported as found.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrattError<T, I = T> {
+    /// The closing bracket of a group, `None` if an operand was expected
+    pub expected: Option<T>,
+    /// The unexpected item, `None` at the end
+    pub found: Option<I>,
+    /// Span of the unexpected item, `None` if tokens have no spans
+    pub span: Option<Span>,
+}
+
+impl<T, I> PrattError<T, I> {
+    pub fn new(expected: Option<T>, found: Option<I>, span: Option<Span>) -> Self {
+        PrattError {
+            expected,
+            found,
+            span,
+        }
+    }
+
+    fn fmt_expected(&self, f: &mut fmt::Formatter) -> fmt::Result
+    where
+        T: fmt::Debug,
+    {
+        match &self.expected {
+            Some(close) => write!(f, "expected {:?}, found ", close),
+            None => write!(f, "expected an operand, found "),
+        }
+    }
+
+    fn fmt_span(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.span {
+            Some(span) => write!(f, " at {}", span.start.offset),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T> fmt::Display for PrattError<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_expected(f)?;
+        match &self.found {
+            Some(tok) => write!(f, "{:?}", tok)?,
+            None => write!(f, "no token")?,
+        }
+
+        self.fmt_span(f)
+    }
+}
+
+impl<T> fmt::Display for PrattError<T, ParseResult<T>>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_expected(f)?;
+        match &self.found {
+            Some(ParseResult::Ok(tok, _)) => write!(f, "{:?}", tok)?,
+            Some(ParseResult::Invalid(error, _)) => write!(f, "invalid token ({})", error)?,
+            Some(ParseResult::Skipped(_)) => write!(f, "unexpected text")?,
+            Some(ParseResult::Error(error)) => return write!(f, "{}", error),
+            None => write!(f, "no token")?,
+        }
+
+        self.fmt_span(f)
+    }
+}
+
+impl<T, I> Error for PrattError<T, I>
+where
+    T: fmt::Debug,
+    I: fmt::Debug,
+    Self: fmt::Display,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+    use crate::parse::Parse;
+    use crate::patterns::pat;
+
+    #[derive(Copy, Clone, PartialEq, Debug, ReadToken)]
+    enum Token {
+        Number(u32),
+        Plus,
+        Minus,
+        Star,
+        Caret,
+        Bang,
+        Open,
+        Close,
+    }
+
+    fn calculator() -> Pratt<'static, Token, String> {
+        let binary = |l: String, op: Token, r: String| format!("({:?} {} {})", op, l, r);
+        Pratt::new(|tok: &Token| match tok {
+            Token::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+        .prefix(Token::Minus, 3, |_, e| format!("(Neg {})", e))
+        .infix(Token::Plus, 1, Assoc::Left, binary)
+        .infix(Token::Minus, 1, Assoc::Left, binary)
+        .infix(Token::Star, 2, Assoc::Left, binary)
+        .infix(Token::Caret, 3, Assoc::Right, binary)
+        .postfix(Token::Bang, 4, |e, _| format!("(Fact {})", e))
+        .group(Token::Open, Token::Close)
+    }
+
+    #[test]
+    fn pratt_slice() {
+        use Token::*;
+
+        let parser = calculator();
+        let cases: [(&[Token], &str); 6] = [
+            (
+                &[Number(1), Plus, Number(2), Star, Number(3)],
+                "(Plus 1 (Star 2 3))",
+            ),
+            (
+                &[Number(1), Minus, Number(2), Minus, Number(3)],
+                "(Minus (Minus 1 2) 3)",
+            ),
+            (
+                &[Number(1), Caret, Number(2), Caret, Number(3)],
+                "(Caret 1 (Caret 2 3))",
+            ),
+            (&[Minus, Number(2), Caret, Number(3)], "(Neg (Caret 2 3))"),
+            (&[Minus, Number(2), Bang], "(Neg (Fact 2))"),
+            (
+                &[Open, Number(1), Plus, Number(2), Close, Star, Number(3)],
+                "(Star (Plus 1 2) 3)",
+            ),
+        ];
+
+        for (tokens, expected) in cases.iter() {
+            let mut source = *tokens;
+            assert_eq!(parser.parse(&mut source), Ok(expected.to_string()));
+            assert!(source.is_empty());
+        }
+
+        let mut source = &[Number(1), Plus, Number(2), Close][..];
+        assert_eq!(parser.parse(&mut source), Ok("(Plus 1 2)".to_string()));
+        assert_eq!(source, [Close]);
+
+        let mut source = &[Number(1), Plus][..];
+        let error = parser.parse(&mut source).unwrap_err();
+        assert_eq!(error, PrattError::new(None, None, None));
+        assert_eq!(error.to_string(), "expected an operand, found no token");
+
+        let mut source = &[Open, Number(1), Number(2)][..];
+        let error = parser.parse(&mut source).unwrap_err();
+        assert_eq!(error, PrattError::new(Some(Close), Some(Number(2)), None));
+        assert_eq!(error.to_string(), "expected Close, found Number(2)");
+        assert_eq!(source, [Number(2)]);
+    }
+
+    #[test]
+    fn pratt_cursor() {
+        let number = pat('0'..='9') * (1..);
+        let lx = lex(number, |n: &str, _| n.parse().map(Token::Number))
+            | lex('+', Token::Plus)
+            | lex('-', Token::Minus)
+            | lex('*', Token::Star)
+            | lex('^', Token::Caret)
+            | lex('!', Token::Bang)
+            | lex('(', Token::Open)
+            | lex(')', Token::Close);
+
+        let parser = calculator();
+        let mut cursor = TokenCursor::new(lx.tokenize("2*(3+-4)!"));
+        let expected = "(Star 2 (Fact (Plus 3 (Neg 4))))";
+        assert_eq!(parser.parse(&mut cursor), Ok(expected.to_string()));
+        assert!(cursor.is_end());
+
+        let code = "1+*2";
+        let mut cursor = TokenCursor::new(lx.tokenize(code));
+        let error = parser.parse(&mut cursor).unwrap_err();
+        assert_eq!(
+            error.found,
+            Some(ParseResult::Ok(Token::Star, Span::of(code, 2..3)))
+        );
+        assert_eq!(error.span, Some(Span::of(code, 2..3)));
+        assert_eq!(error.to_string(), "expected an operand, found Star at 2");
+
+        let mut cursor = TokenCursor::new(lx.tokenize("(1+2"));
+        let error = parser.parse(&mut cursor).unwrap_err();
+        assert_eq!(error.to_string(), "expected Close, found no token at 4");
+
+        let mut cursor = TokenCursor::new(lx.tokenize("1+?"));
+        let error = parser.parse(&mut cursor).unwrap_err();
+        assert!(matches!(error.found, Some(ParseResult::Error(_))));
+        assert_eq!(
+            error.to_string(),
+            "expected an operand, found unexpected '?' at 2"
+        );
+
+        let mut cursor = TokenCursor::new(lx.tokenize("1+99999999999"));
+        let error = parser.parse(&mut cursor).unwrap_err();
+        assert!(matches!(error.found, Some(ParseResult::Invalid(..))));
+    }
+
+    #[test]
+    fn pratt_prefix_precedence() {
+        use Token::*;
+
+        let names = ["a", "b", "c"];
+        let parser = Pratt::new(|tok: &Token| match tok {
+            Number(n) => names.get(*n as usize).map(|name| name.to_string()),
+            _ => None,
+        })
+        .prefix(Minus, 2, |_, e| format!("(Neg {})", e))
+        .infix(Star, 2, Assoc::Left, |l, _, r| {
+            format!("(Star {} {})", l, r)
+        })
+        .infix(Caret, 2, Assoc::Right, |l, _, r| {
+            format!("(Caret {} {})", l, r)
+        });
+
+        let mut source = &[Minus, Number(0), Star, Number(1)][..];
+        assert_eq!(
+            parser.parse(&mut source),
+            Ok("(Star (Neg a) b)".to_string())
+        );
+
+        let mut source = &[Minus, Number(0), Caret, Number(1)][..];
+        assert_eq!(
+            parser.parse(&mut source),
+            Ok("(Neg (Caret a b))".to_string())
+        );
+
+        let mut source = &[Number(3)][..];
+        let error = parser.parse(&mut source).unwrap_err();
+        assert_eq!(error, PrattError::new(None, Some(Number(3)), None));
+    }
+}
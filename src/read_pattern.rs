@@ -1,12 +1,72 @@
+use crate::captures::Captures;
+use crate::first_chars::FirstChars;
+use crate::regex::Regex;
 use std::ops::{Range, RangeInclusive};
 
 pub trait ReadPattern {
     fn read_pattern(&self, text: &str) -> Option<usize>;
 
-    fn read_captures<'t>(&self, text: &'t str, _buf: &mut Vec<&'t str>) -> Option<usize> {
+    /// Returns every length the pattern can match, in order of preference.
+    ///
+    /// Unlike `read_pattern`, which commits to a single length,
+    /// this allows combinators to backtrack like regular expressions do.
+    fn read_pattern_all(&self, text: &str) -> Vec<usize> {
+        self.read_pattern(text).into_iter().collect()
+    }
+
+    /// Returns the furthest position the pattern reaches in the text
+    ///
+    /// Unlike `read_pattern`, this is meaningful when the pattern fails,
+    /// for example an unterminated comment reaches the end of the text.
+    fn read_furthest(&self, text: &str) -> usize {
+        self.read_pattern(text).unwrap_or(0)
+    }
+
+    fn read_captures<'t>(&self, text: &'t str, _caps: &mut Captures<'t>) -> Option<usize> {
         self.read_pattern(text)
     }
 
+    fn captures<'t>(&self, text: &'t str) -> Option<Captures<'t>> {
+        let mut caps = Captures::new(text);
+        let len = self.read_captures(text, &mut caps)?;
+        caps.set_len(len);
+        Some(caps)
+    }
+
+    /// Reads captures of the match which is exactly `len` bytes long.
+    ///
+    /// The `len` is one of the lengths returned by `read_pattern_all`.
+    /// This is how captures are read in backtracking mode.
+    fn read_captures_exact<'t>(&self, text: &'t str, len: usize, _caps: &mut Captures<'t>) -> bool {
+        self.read_pattern_all(text).contains(&len)
+    }
+
+    /// Pushes unset captures for the groups of the pattern
+    ///
+    /// This is done for the branch of `|` which didn't match,
+    /// so positions of the captures don't depend on the branch.
+    fn push_unset_captures(&self, _caps: &mut Captures) {}
+
+    /// Returns true if the pattern has capture groups
+    fn has_captures(&self) -> bool {
+        false
+    }
+
+    /// Returns the set of characters which can start a match
+    ///
+    /// The default is conservative, so the pattern is tried on any text.
+    fn first_chars(&self) -> FirstChars {
+        FirstChars::any()
+    }
+
+    /// Converts the pattern into a regex to compile it
+    ///
+    /// Returns `None` if the pattern can only be interpreted,
+    /// like a closure or an `until` pattern.
+    fn to_regex(&self) -> Option<Regex> {
+        None
+    }
+
     fn test_pattern(&self, text: &str) -> bool {
         match self.read_pattern(text) {
             Some(len) => len == text.len(),
@@ -15,6 +75,20 @@ pub trait ReadPattern {
     }
 }
 
+/// Returns the last char of the range which excludes the `end`
+fn last_before(end: char) -> Option<char> {
+    match end as u32 {
+        0xE000 => Some('\u{D7FF}'),
+        end => end.checked_sub(1).and_then(char::from_u32),
+    }
+}
+
+pub(crate) fn push_len(lens: &mut Vec<usize>, len: usize) {
+    if !lens.contains(&len) {
+        lens.push(len);
+    }
+}
+
 impl ReadPattern for &str {
     fn read_pattern(&self, text: &str) -> Option<usize> {
         if text.starts_with(self) {
@@ -23,12 +97,43 @@ impl ReadPattern for &str {
             None
         }
     }
+
+    fn read_furthest(&self, text: &str) -> usize {
+        text.char_indices()
+            .zip(self.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((pos, ch), _)| pos + ch.len_utf8())
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        match self.chars().next() {
+            Some(ch) => FirstChars::char(ch),
+            None => FirstChars::empty(),
+        }
+    }
+
+    fn to_regex(&self) -> Option<Regex> {
+        Some(Regex::str(self))
+    }
 }
 
 impl ReadPattern for String {
     fn read_pattern(&self, text: &str) -> Option<usize> {
         self.as_str().read_pattern(text)
     }
+
+    fn read_furthest(&self, text: &str) -> usize {
+        self.as_str().read_furthest(text)
+    }
+
+    fn first_chars(&self) -> FirstChars {
+        self.as_str().first_chars()
+    }
+
+    fn to_regex(&self) -> Option<Regex> {
+        self.as_str().to_regex()
+    }
 }
 
 impl ReadPattern for char {
@@ -39,6 +144,14 @@ impl ReadPattern for char {
             None
         }
     }
+
+    fn first_chars(&self) -> FirstChars {
+        FirstChars::char(*self)
+    }
+
+    fn to_regex(&self) -> Option<Regex> {
+        Some(Regex::char(*self))
+    }
 }
 
 impl<F: Fn(char) -> bool> ReadPattern for F {
@@ -48,6 +161,10 @@ impl<F: Fn(char) -> bool> ReadPattern for F {
             _ => None,
         }
     }
+
+    fn first_chars(&self) -> FirstChars {
+        FirstChars::any_char()
+    }
 }
 
 impl ReadPattern for Range<char> {
@@ -59,6 +176,22 @@ impl ReadPattern for Range<char> {
             None
         }
     }
+
+    fn first_chars(&self) -> FirstChars {
+        match last_before(self.end) {
+            Some(last) => FirstChars::range(self.start..=last),
+            None => FirstChars::none(),
+        }
+    }
+
+    fn to_regex(&self) -> Option<Regex> {
+        let ranges = match last_before(self.end) {
+            Some(last) if self.start <= last => vec![self.start..=last],
+            _ => Vec::new(),
+        };
+
+        Some(Regex::Chars(ranges))
+    }
 }
 
 impl ReadPattern for RangeInclusive<char> {
@@ -70,6 +203,20 @@ impl ReadPattern for RangeInclusive<char> {
             None
         }
     }
+
+    fn first_chars(&self) -> FirstChars {
+        FirstChars::range(self.clone())
+    }
+
+    fn to_regex(&self) -> Option<Regex> {
+        let ranges = if self.is_empty() {
+            Vec::new()
+        } else {
+            vec![self.clone()]
+        };
+
+        Some(Regex::Chars(ranges))
+    }
 }
 
 #[cfg(test)]
@@ -97,6 +244,16 @@ mod tests {
         assert_eq!(pattern.read_pattern("прив"), None);
     }
 
+    #[test]
+    fn read_furthest_str() {
+        let pattern = "привет";
+        assert_eq!(pattern.read_furthest("привет!"), pattern.len());
+        assert_eq!(pattern.read_furthest("прив"), "прив".len());
+        assert_eq!(pattern.read_furthest("привык"), "прив".len());
+        assert_eq!(pattern.read_furthest("мир"), 0);
+        assert_eq!('a'.read_furthest("b"), 0);
+    }
+
     #[test]
     fn read_pattern_empty_str() {
         let empty_pattern = "";
@@ -1,24 +1,200 @@
+use crate::captures::Captures;
+use crate::error::TokenError;
+use std::error::Error;
+use std::marker::PhantomData;
+
 pub trait ReadToken<'t> {
     type Token;
-    fn read_token_caps(&self, text: &'t str, caps: &'t [&'t str]) -> Self::Token;
+    fn read_token_caps(&self, text: &'t str, caps: Captures<'t>) -> Self::Token;
 
     fn read_token(&self, text: &'t str) -> Self::Token {
-        self.read_token_caps(text, &[])
+        self.read_token_caps(text, Captures::new(text))
     }
 }
 
+/// Closures get the captures by value, so `|text, caps| ...` needs no annotations,
+/// and the captures are moved into them without a copy
 impl<'t, T, F> ReadToken<'t> for F
 where
     T: ReadToken<'t>,
-    F: Fn(&'t str, &'t [&'t str]) -> T,
+    F: Fn(&'t str, Captures<'t>) -> T,
 {
     type Token = T;
 
-    fn read_token_caps(&self, text: &'t str, caps: &'t [&'t str]) -> Self::Token {
+    fn read_token_caps(&self, text: &'t str, caps: Captures<'t>) -> Self::Token {
         self(text, caps)
     }
 }
 
+/// `()` makes no data, like the constructor of a skipped lexeme
+impl<'t> ReadToken<'t> for () {
+    type Token = ();
+
+    fn read_token_caps(&self, _text: &'t str, _caps: Captures<'t>) {}
+}
+
+/// Fallible conversion of the matched text into a token
+///
+/// Closures which return `Result<T, E>` are fallible, and closures which
+/// return a token always succeed. The derive of `ReadToken` implements
+/// this trait too. Tokens are read by `TryReadTokenWith`, and these methods
+/// are for constructors which need no context.
+///
+pub trait TryReadToken<'t> {
+    type Token;
+
+    fn try_read_token_caps(
+        &self,
+        text: &'t str,
+        caps: Captures<'t>,
+    ) -> Result<Self::Token, TokenError>
+    where
+        Self: TryReadTokenWith<'t, ()>,
+    {
+        self.try_read_token_with(text, caps, &mut ())
+    }
+
+    fn try_read_token(&self, text: &'t str) -> Result<Self::Token, TokenError>
+    where
+        Self: TryReadTokenWith<'t, ()>,
+    {
+        self.try_read_token_caps(text, Captures::new(text))
+    }
+}
+
+/// Fallible conversion with the context `C` of `ParseIterator`
+///
+/// Constructors made by `with_context` read tokens only with the context
+/// of their type, and other constructors with any context.
+///
+pub trait TryReadTokenWith<'t, C>: TryReadToken<'t> {
+    fn try_read_token_with(
+        &self,
+        text: &'t str,
+        caps: Captures<'t>,
+        ctx: &mut C,
+    ) -> Result<Self::Token, TokenError>;
+}
+
+impl<'t, O, F> TryReadToken<'t> for F
+where
+    O: TokenResult<'t>,
+    F: Fn(&'t str, Captures<'t>) -> O,
+{
+    type Token = O::Token;
+}
+
+impl<'t, C, O, F> TryReadTokenWith<'t, C> for F
+where
+    O: TokenResult<'t>,
+    F: Fn(&'t str, Captures<'t>) -> O,
+{
+    fn try_read_token_with(
+        &self,
+        text: &'t str,
+        caps: Captures<'t>,
+        _ctx: &mut C,
+    ) -> Result<Self::Token, TokenError> {
+        self(text, caps).into_token()
+    }
+}
+
+impl<'t> TryReadToken<'t> for () {
+    type Token = ();
+}
+
+impl<'t, C> TryReadTokenWith<'t, C> for () {
+    fn try_read_token_with(
+        &self,
+        _text: &'t str,
+        _caps: Captures<'t>,
+        _ctx: &mut C,
+    ) -> Result<(), TokenError> {
+        Ok(())
+    }
+}
+
+/// Token constructor which gets a mutable context
+///
+/// The context is passed by `ParseIterator::context`, and it must be
+/// of the type `C`. Lexers with the constructor don't parse text
+/// without the context.
+///
+pub struct WithContext<F, C> {
+    read_token: F,
+    context: PhantomData<fn(&mut C)>,
+}
+
+/// Makes a token constructor like `|text, caps, ctx: &mut Ctx| ...`
+pub fn with_context<'t, F, C, O>(read_token: F) -> WithContext<F, C>
+where
+    O: TokenResult<'t>,
+    F: Fn(&'t str, Captures<'t>, &mut C) -> O,
+{
+    WithContext::new(read_token)
+}
+
+impl<F, C> WithContext<F, C> {
+    pub(crate) fn new(read_token: F) -> Self {
+        WithContext {
+            read_token,
+            context: PhantomData,
+        }
+    }
+}
+
+impl<'t, F, C, O> TryReadToken<'t> for WithContext<F, C>
+where
+    O: TokenResult<'t>,
+    F: Fn(&'t str, Captures<'t>, &mut C) -> O,
+{
+    type Token = O::Token;
+}
+
+impl<'t, F, C, O> TryReadTokenWith<'t, C> for WithContext<F, C>
+where
+    O: TokenResult<'t>,
+    F: Fn(&'t str, Captures<'t>, &mut C) -> O,
+{
+    fn try_read_token_with(
+        &self,
+        text: &'t str,
+        caps: Captures<'t>,
+        ctx: &mut C,
+    ) -> Result<Self::Token, TokenError> {
+        (self.read_token)(text, caps, ctx).into_token()
+    }
+}
+
+/// Value returned by a closure of `TryReadToken`
+pub trait TokenResult<'t> {
+    type Token;
+    fn into_token(self) -> Result<Self::Token, TokenError>;
+}
+
+impl<'t, T> TokenResult<'t> for T
+where
+    T: ReadToken<'t>,
+{
+    type Token = T;
+
+    fn into_token(self) -> Result<Self::Token, TokenError> {
+        Ok(self)
+    }
+}
+
+impl<'t, T, E> TokenResult<'t> for Result<T, E>
+where
+    T: ReadToken<'t>,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    type Token = T;
+
+    fn into_token(self) -> Result<Self::Token, TokenError> {
+        self.map_err(TokenError::new)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -49,12 +225,53 @@ mod tests {
         assert_eq!(rt.read_token("C"), Token::Undefined);
     }
 
+    #[test]
+    fn try_read_token() {
+        assert_eq!(Token::A.try_read_token(""), Ok(Token::A));
+
+        let rt = |text: &str, _| match text {
+            "A" => Ok(Token::A),
+            _ => Err(format!("unknown token {}", text)),
+        };
+
+        assert_eq!(rt.try_read_token("A"), Ok(Token::A));
+        let error = rt.try_read_token("C").unwrap_err();
+        assert_eq!(error.to_string(), "unknown token C");
+
+        let rt = |_, _| Token::B;
+        assert_eq!(rt.try_read_token("B"), Ok(Token::B));
+    }
+
+    #[test]
+    fn read_token_ctx() {
+        let rt = with_context(|text: &str, _, count: &mut usize| {
+            *count += 1;
+            match text {
+                "A" => Token::A,
+                _ => Token::B,
+            }
+        });
+
+        let mut count = 0usize;
+        let caps = || Captures::new("A");
+        assert_eq!(
+            rt.try_read_token_with("A", caps(), &mut count),
+            Ok(Token::A)
+        );
+        assert_eq!(
+            rt.try_read_token_with("B", caps(), &mut count),
+            Ok(Token::B)
+        );
+        assert_eq!(count, 2);
+    }
+
     #[derive(Copy, Clone, PartialEq, Debug, ReadToken)]
     enum TokenLT<'t> {
         Text(&'t str),
     }
 
-    fn rt<'t>(text: &'t str, _: &'t [&'t str]) -> TokenLT<'t> {
+    #[allow(clippy::match_single_binding)]
+    fn rt<'t>(text: &'t str, _: Captures<'t>) -> TokenLT<'t> {
         match text {
             t => TokenLT::Text(t),
         }
@@ -63,5 +280,6 @@ mod tests {
     #[test]
     fn read_token_fn_lt() {
         assert_eq!(rt.read_token("text"), TokenLT::Text("text"));
+        assert_eq!(rt.try_read_token("text"), Ok(TokenLT::Text("text")));
     }
 }
@@ -0,0 +1,114 @@
+use crate::bytes::ReadBytes;
+use crate::input::Input;
+use crate::read_pattern::ReadPattern;
+
+/// Strategy of skipping unexpected text
+///
+/// When no lexeme matches, `ParseIterator` skips the text by the strategy
+/// until some lexeme matches again, and reports the whole skipped text
+/// as one error. Strategies of bytes implement `Recovery<[u8]>`.
+///
+pub trait Recovery<I: Input + ?Sized = str> {
+    /// Returns the length of the text to skip, which starts with an unexpected char
+    ///
+    /// At least one char is always skipped, and the length inside of a char
+    /// is rounded up to the end of the char.
+    fn skip(&self, text: &I) -> usize;
+}
+
+impl<F: Fn(&str) -> usize> Recovery for F {
+    fn skip(&self, text: &str) -> usize {
+        self(text)
+    }
+}
+
+impl<F: Fn(&[u8]) -> usize> Recovery<[u8]> for F {
+    fn skip(&self, bytes: &[u8]) -> usize {
+        self(bytes)
+    }
+}
+
+/// Skips one char, or one byte of bytes, at a time
+#[derive(Copy, Clone, Debug)]
+pub struct SkipChar;
+
+impl Recovery for SkipChar {
+    fn skip(&self, text: &str) -> usize {
+        text.chars().next().map_or(0, char::len_utf8)
+    }
+}
+
+impl Recovery<[u8]> for SkipChar {
+    fn skip(&self, bytes: &[u8]) -> usize {
+        bytes.len().min(1)
+    }
+}
+
+/// Skips text until a whitespace, which is ASCII in bytes
+#[derive(Copy, Clone, Debug)]
+pub struct SkipToWhitespace;
+
+impl Recovery for SkipToWhitespace {
+    fn skip(&self, text: &str) -> usize {
+        SkipTo(char::is_whitespace).skip(text)
+    }
+}
+
+impl Recovery<[u8]> for SkipToWhitespace {
+    fn skip(&self, bytes: &[u8]) -> usize {
+        SkipTo(|b: u8| b.is_ascii_whitespace()).skip(bytes)
+    }
+}
+
+/// Skips text until the pattern of a sync set matches
+///
+/// For example, `SkipTo(pat(';') | '}')` skips the rest of a statement,
+/// and `SkipTo(b'\n')` skips the rest of a line of bytes.
+///
+#[derive(Copy, Clone, Debug)]
+pub struct SkipTo<P>(pub P);
+
+impl<P> Recovery for SkipTo<P>
+where
+    P: ReadPattern,
+{
+    fn skip(&self, text: &str) -> usize {
+        text.char_indices()
+            .skip(1)
+            .find(|&(pos, _)| self.0.read_pattern(&text[pos..]).is_some())
+            .map_or(text.len(), |(pos, _)| pos)
+    }
+}
+
+impl<P> Recovery<[u8]> for SkipTo<P>
+where
+    P: ReadBytes,
+{
+    fn skip(&self, bytes: &[u8]) -> usize {
+        (1..bytes.len())
+            .find(|&pos| self.0.read_bytes(&bytes[pos..]).is_some())
+            .unwrap_or(bytes.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::pat;
+
+    #[test]
+    fn recovery() {
+        assert_eq!(SkipChar.skip("ab"), 1);
+        assert_eq!(SkipChar.skip("фы"), 2);
+        assert_eq!(SkipToWhitespace.skip("abc d"), 3);
+        assert_eq!(SkipToWhitespace.skip(" abc"), 4);
+        assert_eq!(SkipTo(pat(';') | '}').skip("a b}c"), 3);
+        assert_eq!(SkipTo("end").skip("фend"), 2);
+        assert_eq!((|text: &str| text.len()).skip("abc"), 3);
+
+        let bytes: &[u8] = b"\xffa\n";
+        assert_eq!(SkipChar.skip(bytes), 1);
+        assert_eq!(SkipTo(b'\n').skip(bytes), 2);
+        assert_eq!(SkipToWhitespace.skip(bytes), 2);
+    }
+}
@@ -0,0 +1,73 @@
+use std::ops::RangeInclusive;
+
+/// Pattern tree in a form which can be compiled into an automaton
+///
+/// Unlike the interpreted patterns, a compiled regex matches
+/// like a regular expression does, so `Alt` and `Repeat` are not greedy
+/// and the longest match wins.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum Regex {
+    /// Any char from the ranges
+    Chars(Vec<RangeInclusive<char>>),
+    /// Sequence of regexes, the empty one matches the empty string
+    Seq(Vec<Regex>),
+    /// Alternation of regexes, the empty one never matches
+    Alt(Vec<Regex>),
+    /// Repetition with the minimal and the optional maximal count
+    Repeat(Box<Regex>, u32, Option<u32>),
+}
+
+impl Regex {
+    pub fn empty() -> Self {
+        Regex::Seq(Vec::new())
+    }
+
+    pub fn never() -> Self {
+        Regex::Alt(Vec::new())
+    }
+
+    pub fn char(ch: char) -> Self {
+        Regex::Chars(vec![ch..=ch])
+    }
+
+    pub fn str(s: &str) -> Self {
+        Regex::Seq(s.chars().map(Regex::char).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::{cap, pat, ANY};
+    use crate::ReadPattern;
+
+    #[test]
+    fn to_regex() {
+        assert_eq!("ab".to_regex(), Some(Regex::str("ab")));
+        assert_eq!('a'.to_regex(), Some(Regex::char('a')));
+        assert_eq!(('a'..'c').to_regex(), Some(Regex::Chars(vec!['a'..='b'])));
+        assert_eq!(char::is_alphabetic.to_regex(), None);
+
+        let pattern = pat(cap('a')) | (pat('b') * (1..3));
+        assert_eq!(
+            pattern.to_regex(),
+            Some(Regex::Alt(vec![
+                Regex::char('a'),
+                Regex::Repeat(Box::new(Regex::char('b')), 1, Some(2)),
+            ]))
+        );
+
+        let pattern = pat('a') & (pat(ANY) * 2);
+        assert_eq!(
+            pattern.to_regex(),
+            Some(Regex::Seq(vec![
+                Regex::char('a'),
+                Regex::Repeat(Box::new(Regex::Chars(vec!['\0'..=char::MAX])), 2, Some(2)),
+            ]))
+        );
+
+        assert_eq!((pat('a') * ..0).to_regex(), Some(Regex::never()));
+        assert_eq!(pat(ANY).until("*/").to_regex(), None);
+    }
+}
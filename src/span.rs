@@ -0,0 +1,143 @@
+use crate::input::Input;
+use std::ops::Range;
+
+/// Column of a position in different units
+///
+/// All columns start from zero.
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Column {
+    pub chars: usize,
+    /// UTF-16 code units, as LSP counts them
+    pub utf16: usize,
+    /// Extended grapheme clusters, as users see them
+    pub graphemes: usize,
+}
+
+/// Position in the text
+///
+/// Lines are separated by `\n` and start from zero.
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Position {
+    /// Byte offset from the start of the text
+    pub offset: usize,
+    pub line: usize,
+    pub column: Column,
+}
+
+impl Position {
+    /// Finds the position of the byte `offset` in the `text`
+    pub fn at<I>(text: &I, offset: usize) -> Self
+    where
+        I: Input + ?Sized,
+    {
+        Position::default().advance(text, offset)
+    }
+
+    /// Moves the position `len` bytes forward in the `text`
+    ///
+    /// The `text` is the whole text, which the position points into,
+    /// so only the passed part of it is scanned.
+    pub fn advance<I>(self, text: &I, len: usize) -> Self
+    where
+        I: Input + ?Sized,
+    {
+        text.advance(self, len)
+    }
+}
+
+/// Span of a token in the text
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Span { start, end }
+    }
+
+    /// Finds the span of the byte `range` in the `text`
+    pub fn of<I>(text: &I, range: Range<usize>) -> Self
+    where
+        I: Input + ?Sized,
+    {
+        let start = Position::at(text, range.start);
+        let end = start.advance(text, range.end - range.start);
+        Span { start, end }
+    }
+
+    /// Byte range of the span
+    pub fn range(&self) -> Range<usize> {
+        self.start.offset..self.end.offset
+    }
+
+    pub fn len(&self) -> usize {
+        self.end.offset - self.start.offset
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position() {
+        let text = "ab\nфы😀\r\nx";
+        let pos = Position::at(text, 0);
+        assert_eq!(pos, Position::default());
+
+        let pos = pos.advance(text, 2);
+        assert_eq!((pos.offset, pos.line, pos.column.chars), (2, 0, 2));
+
+        let pos = pos.advance(text, 1);
+        assert_eq!((pos.offset, pos.line, pos.column.chars), (3, 1, 0));
+
+        let pos = pos.advance(text, "фы😀".len());
+        assert_eq!(
+            pos.column,
+            Column {
+                chars: 3,
+                utf16: 4,
+                graphemes: 3,
+            }
+        );
+
+        let pos = pos.advance(text, 3);
+        assert_eq!((pos.line, pos.column.chars), (2, 1));
+        assert_eq!(pos, Position::at(text, "ab\nфы😀\r\nx".len()));
+    }
+
+    #[test]
+    fn position_graphemes() {
+        // The combining acute accent is a part of the previous cluster
+        let text = "e\u{301}e\u{301}";
+        let pos = Position::default().advance(text, 1);
+        assert_eq!(pos.column.graphemes, 1);
+
+        let pos = pos.advance(text, 2);
+        assert_eq!(pos.column.graphemes, 1);
+        assert_eq!(pos.column.chars, 2);
+
+        let pos = pos.advance(text, 3);
+        assert_eq!(pos.column.graphemes, 2);
+        assert_eq!(pos, Position::at(text, text.len()));
+    }
+
+    #[test]
+    fn span() {
+        let text = "let x";
+        let span = Span::new(Position::at(text, 4), Position::at(text, 5));
+        assert_eq!(span, Span::of(text, 4..5));
+        assert_eq!(span.range(), 4..5);
+        assert_eq!(span.len(), 1);
+        assert!(!span.is_empty());
+        assert_eq!(&text[span.range()], "x");
+    }
+}
@@ -0,0 +1,444 @@
+use crate::error::LexError;
+use crate::parse::{Context, Parse, ParseResult, ParseWith};
+use crate::recovery::Recovery;
+use crate::span::{Position, Span};
+use std::io::{self, BufRead};
+use std::str;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Iterator of tokens of the text read from a `BufRead`
+///
+/// Only a part of the text is kept in the buffer. When some lexeme reaches
+/// the end of the buffered text, whether it matches or not, the iterator
+/// reads more of it, so tokens and UTF-8 sequences may be split across chunks
+/// of the reader, even if a shorter lexeme matches the start of a token.
+/// Tokens can't borrow the buffer, so they must own their data,
+/// like `|n: &str, _: Captures| Token::Name(n.into())` does.
+/// Closures must annotate their arguments to accept text of any lifetime.
+///
+pub struct StreamIterator<'p, P, R, C = ()> {
+    parser: &'p P,
+    reader: R,
+    buffer: String,
+    /// Offset of the rest of the text in the buffer
+    start: usize,
+    /// Offset of the buffer in the whole text
+    base: usize,
+    /// Bytes of an incomplete UTF-8 sequence at the end of the buffer
+    incomplete: Vec<u8>,
+    position: Position,
+    modes: Vec<usize>,
+    recovery: Option<Box<dyn Recovery + 'p>>,
+    context: Context<'p, C>,
+    eof: bool,
+    end: bool,
+}
+
+impl<'p, P, R> StreamIterator<'p, P, R>
+where
+    R: BufRead,
+{
+    pub fn new(parser: &'p P, reader: R) -> Self {
+        StreamIterator {
+            parser,
+            reader,
+            buffer: String::new(),
+            start: 0,
+            base: 0,
+            incomplete: Vec::new(),
+            position: Position::default(),
+            modes: vec![0],
+            recovery: None,
+            context: Context::Owned(()),
+            eof: false,
+            end: false,
+        }
+    }
+}
+
+impl<'p, P, R, C> StreamIterator<'p, P, R, C>
+where
+    R: BufRead,
+{
+    /// Enables the error recovery like `ParseIterator::recover`
+    pub fn recover<S>(mut self, recovery: S) -> Self
+    where
+        S: Recovery + 'p,
+    {
+        self.recovery = Some(Box::new(recovery));
+        self
+    }
+
+    /// Passes the context to token constructors
+    pub fn context<D>(self, ctx: &'p mut D) -> StreamIterator<'p, P, R, D> {
+        StreamIterator {
+            parser: self.parser,
+            reader: self.reader,
+            buffer: self.buffer,
+            start: self.start,
+            base: self.base,
+            incomplete: self.incomplete,
+            position: self.position,
+            modes: self.modes,
+            recovery: self.recovery,
+            context: Context::Borrowed(ctx),
+            eof: self.eof,
+            end: self.end,
+        }
+    }
+
+    /// Current lexer mode
+    pub fn mode(&self) -> usize {
+        self.modes[self.modes.len() - 1]
+    }
+
+    /// Position of the rest of the text
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// Moves the position in the buffer, which starts at `base` of the whole text
+    fn advance(&self, position: Position, len: usize) -> Position {
+        let local = Position {
+            offset: position.offset - self.base,
+            ..position
+        };
+
+        let next = local.advance(self.buffer.as_str(), len);
+        Position {
+            offset: next.offset + self.base,
+            ..next
+        }
+    }
+
+    /// Reads the next chunk of the text into the buffer
+    fn refill(&mut self) -> io::Result<()> {
+        // The last grapheme is kept to count columns of the next one
+        let keep = self.buffer[..self.start]
+            .grapheme_indices(true)
+            .next_back()
+            .map_or(self.start, |(idx, _)| idx);
+
+        self.buffer.drain(..keep);
+        self.start -= keep;
+        self.base += keep;
+
+        let chunk = loop {
+            match self.reader.fill_buf() {
+                Ok(chunk) => break chunk,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        };
+
+        if chunk.is_empty() {
+            self.eof = true;
+            if !self.incomplete.is_empty() {
+                let msg = "stream ended in the middle of a UTF-8 sequence";
+                return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+            }
+
+            return Ok(());
+        }
+
+        let len = chunk.len();
+        self.incomplete.extend_from_slice(chunk);
+        self.reader.consume(len);
+
+        let valid = match str::from_utf8(&self.incomplete) {
+            Ok(text) => text.len(),
+            Err(error) if error.error_len().is_none() => error.valid_up_to(),
+            Err(error) => return Err(io::Error::new(io::ErrorKind::InvalidData, error)),
+        };
+
+        let text = str::from_utf8(&self.incomplete[..valid]).expect("valid UTF-8");
+        self.buffer.push_str(text);
+        self.incomplete.drain(..valid);
+        Ok(())
+    }
+}
+
+impl<'p, P, R, C> StreamIterator<'p, P, R, C>
+where
+    P: for<'t> Parse<'t>,
+    R: BufRead,
+{
+    /// Reads the length of the match in the current mode
+    ///
+    /// More text is read while the match or any lexeme reaches the end
+    /// of the buffer, since a longer match may continue in the next chunk.
+    fn read_len(&mut self) -> io::Result<Option<usize>> {
+        loop {
+            let rest = &self.buffer[self.start..];
+            let mode = self.mode();
+            let len = self.parser.read_len(rest, mode);
+            let furthest = self.parser.read_furthest(rest, mode).len;
+            let complete = len.unwrap_or(0) < rest.len() && furthest < rest.len();
+
+            if complete || self.eof {
+                return Ok(len);
+            }
+
+            self.refill()?;
+        }
+    }
+
+    /// Skips unexpected text by the `recovery` until some lexeme matches
+    fn skip(&mut self, recovery: &dyn Recovery) -> io::Result<Span> {
+        let start = self.position;
+        loop {
+            let rest = &self.buffer[self.start..];
+            let mut len = recovery.skip(rest).clamp(1, rest.len());
+            while !rest.is_char_boundary(len) {
+                len += 1;
+            }
+
+            self.position = self.advance(self.position, len);
+            self.start += len;
+            if self.read_len()?.is_some() || self.start == self.buffer.len() {
+                return Ok(Span::new(start, self.position));
+            }
+        }
+    }
+}
+
+impl<'p, P, R, C, T> Iterator for StreamIterator<'p, P, R, C>
+where
+    P: for<'t> ParseWith<'t, C, Token = T>,
+    R: BufRead,
+{
+    type Item = io::Result<ParseResult<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Skipped lexemes make no item, so the next lexeme is read after them
+        loop {
+            if self.end {
+                return None;
+            }
+
+            let len = match self.read_len() {
+                Ok(len) => len,
+                Err(error) => {
+                    self.end = true;
+                    return Some(Err(error));
+                }
+            };
+
+            let rest = &self.buffer[self.start..];
+            if rest.is_empty() {
+                return None;
+            }
+
+            // The empty skipped lexeme can't move further
+            let start = self.position;
+            let mode = self.mode();
+            let matched = match len {
+                Some(_) => self
+                    .parser
+                    .parse_action(rest, start.offset, mode, self.context.get())
+                    .filter(|(tok, len, _)| *len != 0 || !matches!(tok, Ok(None))),
+                None => None,
+            };
+
+            if let Some((tok, len, action)) = matched {
+                action.apply(&mut self.modes);
+                self.position = self.advance(start, len);
+                self.start += len;
+
+                let span = Span::new(start, self.position);
+                match tok {
+                    Ok(Some(tok)) => return Some(Ok(ParseResult::Ok(tok, span))),
+                    Err(error) => return Some(Ok(ParseResult::Invalid(error, span))),
+                    Ok(None) => continue,
+                }
+            }
+
+            let recovery = match self.recovery.take() {
+                Some(recovery) => recovery,
+                None => {
+                    self.end = true;
+                    let rest = &self.buffer[self.start..];
+                    let furthest = self.parser.read_furthest(rest, mode);
+                    let local = Position {
+                        offset: self.start,
+                        ..start
+                    };
+
+                    let mut error = LexError::new(self.buffer.as_str(), local, furthest);
+                    error.furthest.offset += self.base;
+                    error.position = start;
+                    return Some(Ok(ParseResult::Error(error)));
+                }
+            };
+
+            // The skipped text is reported once, before the next token
+            let skipped = self.skip(&*recovery);
+            self.recovery = Some(recovery);
+            return match skipped {
+                Ok(span) => Some(Ok(ParseResult::Skipped(span))),
+                Err(error) => {
+                    self.end = true;
+                    Some(Err(error))
+                }
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::captures::Captures;
+    use crate::lexer::{lex, skip};
+    use crate::parse::Parse;
+    use crate::patterns::{pat, ANY};
+    use crate::recovery::{SkipChar, SkipTo};
+    use std::io::BufReader;
+    use std::rc::Rc;
+
+    #[derive(Clone, PartialEq, Debug, ReadToken)]
+    enum Token {
+        Name(Rc<str>),
+        Comment,
+        Space,
+        Eq,
+        Slash,
+    }
+
+    #[test]
+    fn stream() {
+        let name = pat(|c: char| c.is_alphanumeric()) * (1..);
+        let comment = pat("/*") & pat(ANY).until("*/");
+        let lx = lex(name, |n: &str, _: Captures| Token::Name(n.into()))
+            | lex(comment, Token::Comment).named("comment")
+            | lex(char::is_whitespace, Token::Space)
+            | lex('=', Token::Eq);
+
+        let text = "let фы =\n/* 😀е\u{301} */ name2  x";
+        let expected: Vec<_> = lx.tokenize(text).collect();
+        assert_eq!(expected.len(), 12);
+
+        for capacity in 1..8 {
+            let reader = BufReader::with_capacity(capacity, text.as_bytes());
+            let tokens: Vec<_> = StreamIterator::new(&lx, reader)
+                .map(Result::unwrap)
+                .collect();
+
+            assert_eq!(tokens, expected);
+        }
+
+        let text = "x = /* y";
+        let expected: Vec<_> = lx.tokenize(text).collect();
+        let reader = BufReader::with_capacity(2, text.as_bytes());
+        let tokens: Vec<_> = StreamIterator::new(&lx, reader)
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(tokens, expected);
+        match tokens.last() {
+            Some(ParseResult::Error(error)) => assert!(error.is_unterminated()),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn stream_prefix_lexeme() {
+        let name = pat(|c: char| c.is_alphanumeric()) * (1..);
+        let comment = pat("/*") & pat(ANY).until("*/");
+        let lx = (lex('/', Token::Slash)
+            | lex(comment, Token::Comment)
+            | lex(name, |n: &str, _: Captures| Token::Name(n.into()))
+            | lex(' ', Token::Space))
+        .longest();
+
+        let text = "a /* abc */ / b";
+        let expected: Vec<_> = lx.tokenize(text).collect();
+        assert_eq!(
+            expected[2],
+            ParseResult::Ok(Token::Comment, Span::of(text, 2..11))
+        );
+
+        for capacity in 1..12 {
+            let reader = BufReader::with_capacity(capacity, text.as_bytes());
+            let tokens: Vec<_> = StreamIterator::new(&lx, reader)
+                .map(Result::unwrap)
+                .collect();
+
+            assert_eq!(tokens, expected, "{}", capacity);
+        }
+    }
+
+    #[test]
+    fn stream_invalid_utf8() {
+        let lx = lex(pat('a') * (1..), |n: &str, _: Captures| {
+            Token::Name(n.into())
+        });
+
+        let reader = BufReader::with_capacity(2, &b"aa\xffa"[..]);
+        let mut iter = StreamIterator::new(&lx, reader);
+        let error = iter.find_map(Result::err).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let reader = BufReader::with_capacity(2, "aaф".as_bytes().split_last().unwrap().1);
+        let items: Vec<_> = StreamIterator::new(&lx, reader).collect();
+        assert_eq!(items.len(), 1);
+        assert!(items[0].is_err());
+    }
+
+    #[test]
+    fn stream_skip() {
+        let name = pat(|c: char| c.is_alphanumeric()) * (1..);
+        let lx = skip(pat(char::is_whitespace) * (1..))
+            | lex(name, |n: &str, _: Captures| Token::Name(n.into()))
+            | lex('=', Token::Eq);
+
+        let text = "  a   = b2    ";
+        let expected: Vec<_> = lx.tokenize(text).collect();
+        assert_eq!(expected.len(), 3);
+
+        for capacity in 1..4 {
+            let reader = BufReader::with_capacity(capacity, text.as_bytes());
+            let tokens: Vec<_> = StreamIterator::new(&lx, reader)
+                .map(Result::unwrap)
+                .collect();
+
+            assert_eq!(tokens, expected);
+        }
+    }
+
+    #[test]
+    fn stream_recover() {
+        let name = pat(|c: char| c.is_alphanumeric()) * (1..);
+        let comment = pat("/*") & pat(ANY).until("*/");
+        let lx = lex(name, |n: &str, _: Captures| Token::Name(n.into()))
+            | lex(comment, Token::Comment)
+            | lex(' ', Token::Space)
+            | lex('=', Token::Eq);
+
+        let text = "a = ;;€; b /* c";
+        let expected: Vec<_> = lx.tokenize(text).recover(SkipChar).collect();
+        assert_eq!(expected[4], ParseResult::Skipped(Span::of(text, 4..10)));
+        assert_eq!(expected.len(), 11);
+
+        for capacity in 1..6 {
+            let reader = BufReader::with_capacity(capacity, text.as_bytes());
+            let tokens: Vec<_> = StreamIterator::new(&lx, reader)
+                .recover(SkipChar)
+                .map(Result::unwrap)
+                .collect();
+
+            assert_eq!(tokens, expected);
+        }
+
+        let reader = BufReader::with_capacity(3, text.as_bytes());
+        let tokens: Vec<_> = StreamIterator::new(&lx, reader)
+            .recover(SkipTo(' '))
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(
+            tokens,
+            lx.tokenize(text).recover(SkipTo(' ')).collect::<Vec<_>>()
+        );
+    }
+}
@@ -2,12 +2,11 @@
 
 use crate::patterns::{pat, ANY};
 use crate::ReadToken;
-use crate::{lex, Parse, ParseResult};
+use crate::{lex, DelimiterError, Parse, ParseResult, Span, TokenTree, TreeBuilder};
 use std::str::FromStr;
 
 #[derive(Copy, Clone, PartialEq, Debug, ReadToken)]
 enum Token<'t> {
-    Empty,
     NewLine,
     Plus,
     Star,
@@ -30,7 +29,7 @@ fn test_lexer() {
     let number = pat('0'..='9') * (1..);
     let comment = pat("/*") & pat(ANY).until("*/");
 
-    let lx = lex(' ', Token::Empty)
+    let lx = lex(' ', ()).skip()
         | lex('\n', Token::NewLine)
         | lex('+', Token::Plus)
         | lex('*', Token::Star)
@@ -38,7 +37,7 @@ fn test_lexer() {
         | lex('(', Token::LeftBracket)
         | lex(')', Token::RightBracket)
         | lex(';', Token::Semicolon)
-        | lex(number, |n, _| Token::Number(u32::from_str(n).unwrap()))
+        | lex(number, |n, _| u32::from_str(n).map(Token::Number))
         | lex(name, |n, _| match n {
             "let" => Token::Let,
             "if" => Token::If,
@@ -58,9 +57,8 @@ fn test_lexer() {
         .tokenize(code.as_str())
         .map(|r| match r {
             ParseResult::Ok(tok, _) => tok,
-            ParseResult::UnexpectedAt(_) => unreachable!(),
+            _ => unreachable!(),
         })
-        .filter(|t| *t != Token::Empty)
         .collect();
 
     assert_eq!(
@@ -93,3 +91,45 @@ fn test_lexer() {
         ]
     );
 }
+
+#[test]
+fn test_token_tree() {
+    let name = pat('a'..='z') * (1..);
+    let lx = lex(' ', ()).skip()
+        | lex('(', Token::LeftBracket)
+        | lex(')', Token::RightBracket)
+        | lex('+', Token::Plus)
+        | lex(name, |n, _| Token::Name(n));
+
+    let tokens = |code| {
+        lx.tokenize(code)
+            .filter_map(|r| match r {
+                ParseResult::Ok(tok, span) => Some((tok, span)),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let builder = TreeBuilder::new().delimiters(Token::LeftBracket, Token::RightBracket);
+
+    let code = "f (a + (b)) c";
+    let trees = builder.build(tokens(code)).unwrap();
+    assert_eq!(trees.len(), 3);
+    match &trees[1] {
+        TokenTree::Group(group) => {
+            assert_eq!(group.span(), Span::of(code, 2..11));
+            assert_eq!(group.trees.len(), 3);
+            assert_eq!(group.trees[2].span(), Span::of(code, 7..10));
+        }
+        _ => unreachable!(),
+    }
+
+    let code = "f (a + (b) c";
+    match *builder.build(tokens(code)).unwrap_err() {
+        DelimiterError::Unclosed { open_span, end, .. } => {
+            assert_eq!(open_span, Span::of(code, 2..3));
+            assert_eq!(end, Span::of(code, 12..12));
+        }
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,465 @@
+use crate::cursor::TokenCursor;
+use crate::parse::{ParseResult, ParseWith};
+use crate::patterns::{
+    AndPattern, AnyPattern, ManyPattern, OrPattern, Pattern, RangePattern, UntilPattern,
+};
+use crate::span::Span;
+use std::mem::{self, Discriminant};
+use std::ops::{Bound, RangeBounds};
+
+/// Pattern of tokens, like `ReadPattern` is of text
+pub trait ReadTokens<T> {
+    /// Returns the count of matched tokens
+    fn read_tokens(&self, tokens: &[T]) -> Option<usize>;
+
+    /// Consumes matched tokens of the cursor
+    ///
+    /// Tokens are read lazily up to the first item which isn't a token.
+    /// If the pattern doesn't match, the cursor is rewound and `false` is returned.
+    fn read_cursor<'p, 't, Q, C>(&self, cursor: &mut TokenCursor<'p, 't, Q, C>) -> bool
+    where
+        Q: ParseWith<'t, C, Token = T>;
+
+    fn test_tokens(&self, tokens: &[T]) -> bool {
+        self.read_tokens(tokens) == Some(tokens.len())
+    }
+
+    /// Matches tokens of the cursor and returns them with their span
+    ///
+    /// If nothing was matched, the span is empty at the next item.
+    fn read_parsed<'p, 't, Q, C>(
+        &self,
+        cursor: &mut TokenCursor<'p, 't, Q, C>,
+    ) -> Option<(Vec<T>, Span)>
+    where
+        Q: ParseWith<'t, C, Token = T>,
+        T: Clone,
+    {
+        let start = cursor.checkpoint();
+        let first = cursor.consumed().len();
+        if !self.read_cursor(cursor) {
+            return None;
+        }
+
+        let tokens = cursor.consumed()[first..]
+            .iter()
+            .filter_map(|item| match item {
+                ParseResult::Ok(tok, _) => Some(tok.clone()),
+                _ => None,
+            })
+            .collect();
+
+        Some((tokens, cursor.span_since(start)))
+    }
+}
+
+/// Consumes the next item if it's a token matching `f`
+fn read_token<'p, 't, Q, C, F>(cursor: &mut TokenCursor<'p, 't, Q, C>, f: F) -> bool
+where
+    Q: ParseWith<'t, C>,
+    F: FnOnce(&Q::Token) -> bool,
+{
+    match cursor.peek() {
+        Some(ParseResult::Ok(tok, _)) if f(tok) => {
+            cursor.advance();
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Matches the token equal to the given one
+#[derive(Copy, Clone, Debug)]
+pub struct TokenEq<T>(pub T);
+
+impl<T: PartialEq> ReadTokens<T> for TokenEq<T> {
+    fn read_tokens(&self, tokens: &[T]) -> Option<usize> {
+        match tokens.first() {
+            Some(tok) if *tok == self.0 => Some(1),
+            _ => None,
+        }
+    }
+
+    fn read_cursor<'p, 't, Q, C>(&self, cursor: &mut TokenCursor<'p, 't, Q, C>) -> bool
+    where
+        Q: ParseWith<'t, C, Token = T>,
+    {
+        read_token(cursor, |tok| *tok == self.0)
+    }
+}
+
+/// Matches any token of the same variant
+#[derive(Debug)]
+pub struct TokenKind<T>(pub Discriminant<T>);
+
+impl<T> Clone for TokenKind<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for TokenKind<T> {}
+
+impl<T> ReadTokens<T> for TokenKind<T> {
+    fn read_tokens(&self, tokens: &[T]) -> Option<usize> {
+        match tokens.first() {
+            Some(tok) if mem::discriminant(tok) == self.0 => Some(1),
+            _ => None,
+        }
+    }
+
+    fn read_cursor<'p, 't, Q, C>(&self, cursor: &mut TokenCursor<'p, 't, Q, C>) -> bool
+    where
+        Q: ParseWith<'t, C, Token = T>,
+    {
+        read_token(cursor, |tok| mem::discriminant(tok) == self.0)
+    }
+}
+
+impl<T, F: Fn(&T) -> bool> ReadTokens<T> for F {
+    fn read_tokens(&self, tokens: &[T]) -> Option<usize> {
+        match tokens.first() {
+            Some(tok) if self(tok) => Some(1),
+            _ => None,
+        }
+    }
+
+    fn read_cursor<'p, 't, Q, C>(&self, cursor: &mut TokenCursor<'p, 't, Q, C>) -> bool
+    where
+        Q: ParseWith<'t, C, Token = T>,
+    {
+        read_token(cursor, self)
+    }
+}
+
+/// `ANY` matches any token
+impl<T> ReadTokens<T> for AnyPattern {
+    fn read_tokens(&self, tokens: &[T]) -> Option<usize> {
+        if tokens.is_empty() {
+            None
+        } else {
+            Some(1)
+        }
+    }
+
+    fn read_cursor<'p, 't, Q, C>(&self, cursor: &mut TokenCursor<'p, 't, Q, C>) -> bool
+    where
+        Q: ParseWith<'t, C, Token = T>,
+    {
+        read_token(cursor, |_| true)
+    }
+}
+
+impl<T, L, R> ReadTokens<T> for AndPattern<L, R>
+where
+    L: ReadTokens<T>,
+    R: ReadTokens<T>,
+{
+    fn read_tokens(&self, tokens: &[T]) -> Option<usize> {
+        let len_a = self.0.read_tokens(tokens)?;
+        let len_b = self.1.read_tokens(&tokens[len_a..])?;
+        Some(len_a + len_b)
+    }
+
+    fn read_cursor<'p, 't, Q, C>(&self, cursor: &mut TokenCursor<'p, 't, Q, C>) -> bool
+    where
+        Q: ParseWith<'t, C, Token = T>,
+    {
+        let start = cursor.checkpoint();
+        if self.0.read_cursor(cursor) && self.1.read_cursor(cursor) {
+            return true;
+        }
+
+        cursor.rewind(start);
+        false
+    }
+}
+
+impl<T, L, R> ReadTokens<T> for OrPattern<L, R>
+where
+    L: ReadTokens<T>,
+    R: ReadTokens<T>,
+{
+    fn read_tokens(&self, tokens: &[T]) -> Option<usize> {
+        self.0
+            .read_tokens(tokens)
+            .or_else(|| self.1.read_tokens(tokens))
+    }
+
+    fn read_cursor<'p, 't, Q, C>(&self, cursor: &mut TokenCursor<'p, 't, Q, C>) -> bool
+    where
+        Q: ParseWith<'t, C, Token = T>,
+    {
+        self.0.read_cursor(cursor) || self.1.read_cursor(cursor)
+    }
+}
+
+impl<T, P> ReadTokens<T> for ManyPattern<P>
+where
+    P: ReadTokens<T>,
+{
+    fn read_tokens(&self, tokens: &[T]) -> Option<usize> {
+        let mut len = 0;
+        for _ in 0..self.1 {
+            len += self.0.read_tokens(&tokens[len..])?;
+        }
+
+        Some(len)
+    }
+
+    fn read_cursor<'p, 't, Q, C>(&self, cursor: &mut TokenCursor<'p, 't, Q, C>) -> bool
+    where
+        Q: ParseWith<'t, C, Token = T>,
+    {
+        let start = cursor.checkpoint();
+        for _ in 0..self.1 {
+            if !self.0.read_cursor(cursor) {
+                cursor.rewind(start);
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl<T, P, R> ReadTokens<T> for RangePattern<P, R>
+where
+    P: ReadTokens<T>,
+    R: RangeBounds<u32>,
+{
+    fn read_tokens(&self, tokens: &[T]) -> Option<usize> {
+        if self.0.read_tokens(&[]).is_some() && self.1.end_bound() == Bound::Unbounded {
+            panic!("Infinity loop")
+        }
+
+        let mut len = 0;
+        let mut count = 0;
+        while self.can_repeat(count) {
+            match self.0.read_tokens(&tokens[len..]) {
+                Some(l) => {
+                    len += l;
+                    count += 1;
+                }
+                None => break,
+            }
+        }
+
+        if self.1.contains(&count) {
+            Some(len)
+        } else {
+            None
+        }
+    }
+
+    fn read_cursor<'p, 't, Q, C>(&self, cursor: &mut TokenCursor<'p, 't, Q, C>) -> bool
+    where
+        Q: ParseWith<'t, C, Token = T>,
+    {
+        if self.0.read_tokens(&[]).is_some() && self.1.end_bound() == Bound::Unbounded {
+            panic!("Infinity loop")
+        }
+
+        let start = cursor.checkpoint();
+        let mut count = 0;
+        while self.can_repeat(count) && self.0.read_cursor(cursor) {
+            count += 1;
+        }
+
+        if self.1.contains(&count) {
+            true
+        } else {
+            cursor.rewind(start);
+            false
+        }
+    }
+}
+
+impl<T, P, U> ReadTokens<T> for UntilPattern<P, U>
+where
+    P: ReadTokens<T>,
+    U: ReadTokens<T>,
+{
+    fn read_tokens(&self, tokens: &[T]) -> Option<usize> {
+        if self.0.read_tokens(&[]).is_some() {
+            panic!("Infinity loop")
+        }
+
+        let mut len = 0;
+        loop {
+            let rest = &tokens[len..];
+            match self.1.read_tokens(rest) {
+                None => len += self.0.read_tokens(rest)?,
+                Some(end_len) => break Some(len + end_len),
+            }
+        }
+    }
+
+    fn read_cursor<'p, 't, Q, C>(&self, cursor: &mut TokenCursor<'p, 't, Q, C>) -> bool
+    where
+        Q: ParseWith<'t, C, Token = T>,
+    {
+        if self.0.read_tokens(&[]).is_some() {
+            panic!("Infinity loop")
+        }
+
+        let start = cursor.checkpoint();
+        loop {
+            if self.1.read_cursor(cursor) {
+                return true;
+            }
+
+            if !self.0.read_cursor(cursor) {
+                cursor.rewind(start);
+                return false;
+            }
+        }
+    }
+}
+
+/// Wraps a token pattern to combine it with operators
+///
+/// Like `tok(Token::Let) & name & tok(Token::Eq) & value`,
+/// where `name` and `value` are patterns or closures like `|t: &Token| ...`.
+///
+pub fn tpat<T>(pattern: T) -> Pattern<T> {
+    Pattern(pattern)
+}
+
+pub fn tok<T: PartialEq>(token: T) -> Pattern<TokenEq<T>> {
+    Pattern(TokenEq(token))
+}
+
+/// Matches tokens of the same variant as the `token`, whatever their data is
+pub fn kind<T>(token: &T) -> Pattern<TokenKind<T>> {
+    Pattern(TokenKind(mem::discriminant(token)))
+}
+
+impl<T, P> ReadTokens<T> for Pattern<P>
+where
+    P: ReadTokens<T>,
+{
+    fn read_tokens(&self, tokens: &[T]) -> Option<usize> {
+        self.0.read_tokens(tokens)
+    }
+
+    fn read_cursor<'p, 't, Q, C>(&self, cursor: &mut TokenCursor<'p, 't, Q, C>) -> bool
+    where
+        Q: ParseWith<'t, C, Token = T>,
+    {
+        self.0.read_cursor(cursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+    use crate::parse::Parse;
+    use crate::patterns::{pat, ANY};
+
+    #[derive(Copy, Clone, PartialEq, Debug, ReadToken)]
+    enum Token<'t> {
+        Let,
+        Name(&'t str),
+        Number(&'t str),
+        Eq,
+        Plus,
+        Semi,
+    }
+
+    #[test]
+    fn token_patterns() {
+        let name = |t: &Token| matches!(t, Token::Name(_));
+        let value = tpat(name) | kind(&Token::Number(""));
+        let expr = value & ((tok(Token::Plus) & value) * ..);
+        let stmt = tok(Token::Let) & name & tok(Token::Eq) & expr & tok(Token::Semi);
+
+        let tokens = [
+            Token::Let,
+            Token::Name("x"),
+            Token::Eq,
+            Token::Number("1"),
+            Token::Plus,
+            Token::Name("y"),
+            Token::Semi,
+            Token::Let,
+        ];
+
+        assert_eq!(stmt.read_tokens(&tokens), Some(7));
+        assert!(!stmt.test_tokens(&tokens));
+        assert!(stmt.test_tokens(&tokens[..7]));
+        assert_eq!(stmt.read_tokens(&tokens[..6]), None);
+        assert_eq!(expr.read_tokens(&tokens[3..]), Some(3));
+
+        assert_eq!((tpat(ANY) * 2).read_tokens(&tokens[..1]), None);
+        assert_eq!((tok(Token::Let) * (1..=2)).read_tokens(&tokens), Some(1));
+        assert_eq!(
+            tpat(ANY).until(tok(Token::Semi)).read_tokens(&tokens),
+            Some(7)
+        );
+        assert_eq!(
+            tpat(name).until(tok(Token::Semi)).read_tokens(&tokens),
+            None
+        );
+    }
+
+    #[test]
+    fn token_patterns_parsed() {
+        let name = pat('a'..='z') * (1..);
+        let number = pat('0'..='9') * (1..);
+        let lx = lex("let", Token::Let)
+            | lex(name, |n, _| Token::Name(n))
+            | lex(number, |n, _| Token::Number(n))
+            | lex('=', Token::Eq)
+            | lex('+', Token::Plus)
+            | lex(';', Token::Semi);
+
+        let assign = kind(&Token::Name("")) & tok(Token::Eq) & kind(&Token::Number(""));
+        let code = "x=1;y=2";
+        let mut cursor = TokenCursor::new(lx.tokenize(code));
+        let (tokens, span) = assign.read_parsed(&mut cursor).unwrap();
+        assert_eq!(tokens, [Token::Name("x"), Token::Eq, Token::Number("1")]);
+        assert_eq!(span, Span::of(code, 0..3));
+        assert_eq!(
+            cursor.expect(&Token::Semi),
+            Ok((Token::Semi, Span::of(code, 3..4)))
+        );
+
+        let stmt = assign & tok(Token::Semi);
+        assert_eq!(stmt.read_parsed(&mut cursor), None);
+        assert_eq!(cursor.consumed().len(), 4);
+        assert_eq!(
+            assign.read_parsed(&mut cursor).unwrap().1,
+            Span::of(code, 4..7)
+        );
+
+        let mut cursor = TokenCursor::new(lx.tokenize("x=1?"));
+        let (tokens, span) = (tpat(ANY) * ..).read_parsed(&mut cursor).unwrap();
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(span, Span::of("x=1?", 0..3));
+
+        let mut cursor = TokenCursor::new(lx.tokenize("?"));
+        let (tokens, span) = (tok(Token::Semi) * ..).read_parsed(&mut cursor).unwrap();
+        assert!(tokens.is_empty());
+        assert!(span.is_empty());
+
+        let mut cursor = TokenCursor::new(lx.tokenize("x=y"));
+        assert_eq!(assign.read_parsed(&mut cursor), None);
+        assert_eq!(cursor.consumed().len(), 0);
+
+        let mut cursor = TokenCursor::new(lx.tokenize("x+y;"));
+        let until = tpat(ANY).until(tok(Token::Semi));
+        assert_eq!(until.read_parsed(&mut cursor).unwrap().0.len(), 4);
+        assert!(cursor.is_end());
+    }
+
+    #[test]
+    #[should_panic(expected = "Infinity loop")]
+    fn until_nullable_tokens() {
+        let tokens = [Token::Plus, Token::Semi];
+        tpat(tok(Token::Plus) * ..)
+            .until(tok(Token::Semi))
+            .read_tokens(&tokens);
+    }
+}
@@ -0,0 +1,275 @@
+use crate::span::Span;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenTree<T> {
+    Token(T, Span),
+    Group(Group<T>),
+}
+
+impl<T> TokenTree<T> {
+    pub fn span(&self) -> Span {
+        match self {
+            TokenTree::Token(_, span) => *span,
+            TokenTree::Group(group) => group.span(),
+        }
+    }
+}
+
+/// Tokens between a pair of delimiters
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group<T> {
+    pub open: T,
+    pub close: T,
+    pub open_span: Span,
+    pub close_span: Span,
+    pub trees: Vec<TokenTree<T>>,
+}
+
+impl<T> Group<T> {
+    /// Span of the group including delimiters
+    pub fn span(&self) -> Span {
+        Span::new(self.open_span.start, self.close_span.end)
+    }
+}
+
+/// Builder of token trees, which groups tokens between delimiters
+///
+/// Tokens of a `ParseIterator` are passed with their spans,
+/// so skipped text and errors are handled before building trees.
+///
+pub struct TreeBuilder<T> {
+    delimiters: Vec<(T, T)>,
+}
+
+impl<T> Default for TreeBuilder<T> {
+    fn default() -> Self {
+        TreeBuilder {
+            delimiters: Vec::new(),
+        }
+    }
+}
+
+/// Token trees, or the first error of delimiters
+pub type TreeResult<T> = Result<Vec<TokenTree<T>>, Box<DelimiterError<T>>>;
+
+impl<T> TreeBuilder<T>
+where
+    T: PartialEq,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the pair of delimiters, like `(Token::LeftBracket, Token::RightBracket)`
+    pub fn delimiters(mut self, open: T, close: T) -> Self {
+        self.delimiters.push((open, close));
+        self
+    }
+
+    pub fn build<I>(&self, tokens: I) -> TreeResult<T>
+    where
+        I: IntoIterator<Item = (T, Span)>,
+    {
+        // Opened groups with the index of their delimiters
+        let mut stack: Vec<(T, Span, usize, Vec<TokenTree<T>>)> = Vec::new();
+        let mut trees = Vec::new();
+        let mut end = None;
+
+        for (tok, span) in tokens {
+            end = Some(span.end);
+
+            if let Some(idx) = self.delimiters.iter().position(|(open, _)| *open == tok) {
+                stack.push((tok, span, idx, Vec::new()));
+                continue;
+            }
+
+            if !self.delimiters.iter().any(|(_, close)| *close == tok) {
+                let tree = TokenTree::Token(tok, span);
+                match stack.last_mut() {
+                    Some((.., group)) => group.push(tree),
+                    None => trees.push(tree),
+                }
+
+                continue;
+            }
+
+            let (open, open_span, idx, group) = match stack.pop() {
+                Some(opened) => opened,
+                None => {
+                    return Err(Box::new(DelimiterError::Unopened {
+                        close: tok,
+                        close_span: span,
+                    }))
+                }
+            };
+
+            if self.delimiters[idx].1 != tok {
+                return Err(Box::new(DelimiterError::Mismatched {
+                    open,
+                    open_span,
+                    close: tok,
+                    close_span: span,
+                }));
+            }
+
+            let tree = TokenTree::Group(Group {
+                open,
+                close: tok,
+                open_span,
+                close_span: span,
+                trees: group,
+            });
+
+            match stack.last_mut() {
+                Some((.., group)) => group.push(tree),
+                None => trees.push(tree),
+            }
+        }
+
+        match stack.pop() {
+            Some((open, open_span, ..)) => {
+                let end = end.unwrap_or(open_span.end);
+                Err(Box::new(DelimiterError::Unclosed {
+                    open,
+                    open_span,
+                    end: Span::new(end, end),
+                }))
+            }
+            None => Ok(trees),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DelimiterError<T> {
+    /// The closing delimiter doesn't match the opening one
+    Mismatched {
+        open: T,
+        open_span: Span,
+        close: T,
+        close_span: Span,
+    },
+    /// The opening delimiter isn't closed until the `end` of tokens
+    Unclosed { open: T, open_span: Span, end: Span },
+    /// The closing delimiter has no opening one
+    Unopened { close: T, close_span: Span },
+}
+
+impl<T> fmt::Display for DelimiterError<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DelimiterError::Mismatched {
+                open,
+                open_span,
+                close,
+                close_span,
+            } => write!(
+                f,
+                "mismatched {:?} at {} closing {:?} at {}",
+                close, close_span.start.offset, open, open_span.start.offset
+            ),
+            DelimiterError::Unclosed {
+                open,
+                open_span,
+                end,
+            } => write!(
+                f,
+                "unclosed {:?} at {} until the end at {}",
+                open, open_span.start.offset, end.start.offset
+            ),
+            DelimiterError::Unopened { close, close_span } => {
+                write!(f, "unexpected {:?} at {}", close, close_span.start.offset)
+            }
+        }
+    }
+}
+
+impl<T> Error for DelimiterError<T> where T: fmt::Debug {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+    use crate::parse::{Parse, ParseResult};
+
+    #[derive(Copy, Clone, PartialEq, Debug, ReadToken)]
+    enum Token {
+        X,
+        Paren(bool),
+        Brace(bool),
+    }
+
+    fn tokens(code: &str) -> Vec<(Token, Span)> {
+        let lx = lex('x', Token::X)
+            | lex('(', Token::Paren(true))
+            | lex(')', Token::Paren(false))
+            | lex('{', Token::Brace(true))
+            | lex('}', Token::Brace(false));
+
+        lx.tokenize(code)
+            .filter_map(|r| match r {
+                ParseResult::Ok(tok, span) => Some((tok, span)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn token_tree() {
+        let builder = TreeBuilder::new()
+            .delimiters(Token::Paren(true), Token::Paren(false))
+            .delimiters(Token::Brace(true), Token::Brace(false));
+
+        let code = "x(x{})";
+        let trees = builder.build(tokens(code)).unwrap();
+        assert_eq!(trees.len(), 2);
+        assert_eq!(trees[1].span(), Span::of(code, 1..6));
+
+        let group = match &trees[1] {
+            TokenTree::Group(group) => group,
+            _ => unreachable!(),
+        };
+        assert_eq!(group.open, Token::Paren(true));
+        assert_eq!(
+            group.trees[0],
+            TokenTree::Token(Token::X, Span::of(code, 2..3))
+        );
+        match &group.trees[1] {
+            TokenTree::Group(inner) => {
+                assert!(inner.trees.is_empty());
+                assert_eq!(inner.span(), Span::of(code, 3..5));
+            }
+            _ => unreachable!(),
+        }
+
+        let code = "(x{x)}";
+        let error = builder.build(tokens(code)).unwrap_err();
+        assert_eq!(
+            *error,
+            DelimiterError::Mismatched {
+                open: Token::Brace(true),
+                open_span: Span::of(code, 2..3),
+                close: Token::Paren(false),
+                close_span: Span::of(code, 4..5),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "mismatched Paren(false) at 4 closing Brace(true) at 2"
+        );
+
+        let error = builder.build(tokens("x(x{}")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unclosed Paren(true) at 1 until the end at 5"
+        );
+
+        let error = builder.build(tokens("x})")).unwrap_err();
+        assert_eq!(error.to_string(), "unexpected Brace(false) at 1");
+    }
+}